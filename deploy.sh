@@ -74,18 +74,21 @@ setup_firewall() {
     if command -v ufw &> /dev/null; then
         print_info "Using UFW to open port $port"
         sudo ufw allow $port/tcp
+        sudo ufw allow $port/udp
         sudo ufw --force enable
         print_success "UFW rule added for port $port"
     # Check if firewall-cmd is available (CentOS/RHEL)
     elif command -v firewall-cmd &> /dev/null; then
         print_info "Using firewall-cmd to open port $port"
         sudo firewall-cmd --permanent --add-port=$port/tcp
+        sudo firewall-cmd --permanent --add-port=$port/udp
         sudo firewall-cmd --reload
         print_success "Firewall rule added for port $port"
     # Check if iptables is available
     elif command -v iptables &> /dev/null; then
         print_info "Using iptables to open port $port"
         sudo iptables -A INPUT -p tcp --dport $port -j ACCEPT
+        sudo iptables -A INPUT -p udp --dport $port -j ACCEPT
         # Try to save iptables rules
         if command -v iptables-save &> /dev/null; then
             sudo iptables-save > /etc/iptables/rules.v4 2>/dev/null || true
@@ -137,6 +140,7 @@ deploy() {
         echo "  - P2P port: $P2P_PORT"
         echo "  - Public IP: $public_ip"
         echo "  - Multiaddr: /ip4/$public_ip/tcp/$P2P_PORT/p2p/[PEER_ID]"
+        echo "  - QUIC:      /ip4/$public_ip/udp/$P2P_PORT/quic-v1/p2p/[PEER_ID]"
         echo ""
         print_info "To connect to this node from another peer, use:"
         echo "  ./node_eeb --connect /ip4/$public_ip/tcp/$P2P_PORT/p2p/[PEER_ID]"
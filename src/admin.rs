@@ -0,0 +1,218 @@
+use anyhow::{anyhow, Context, Result};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use libp2p::{Multiaddr, PeerId};
+use serde::Deserialize;
+use serde_json::json;
+use std::{net::SocketAddr, time::Duration};
+use tokio::{sync::oneshot, task::JoinHandle};
+use tracing::{error, info};
+
+use crate::command::{
+    GetRecordOutcome, ListenAddrs, PeerInfo, PutRecordOptions, PutRecordOutcome, RecordQuorum, TopicInfo,
+};
+use crate::handle::{NodeHandle, NodeStopped};
+
+#[derive(Debug, Deserialize)]
+struct DialRequest {
+    addr: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TopicRequest {
+    topic: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PublishRequest {
+    topic: String,
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetRecordQuery {
+    quorum: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PutRecordRequest {
+    value: String,
+    quorum: Option<String>,
+    ttl_secs: Option<u64>,
+    publisher: Option<String>,
+}
+
+/// Error returned to HTTP clients as `{"error": "..."}`.
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        if e.is::<NodeStopped>() {
+            ApiError(StatusCode::SERVICE_UNAVAILABLE, e.to_string())
+        } else {
+            bad_request(e)
+        }
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+/// Bind the admin API on `addr` and serve it in the background until `stop`
+/// fires or is dropped.
+///
+/// Every handler goes through a [`NodeHandle`], so a slow client never blocks
+/// event processing.
+pub(crate) async fn spawn(addr: SocketAddr, node: NodeHandle, stop: oneshot::Receiver<()>) -> Result<JoinHandle<()>> {
+    let app = Router::new()
+        .route("/peers", get(peers))
+        .route("/listen-addrs", get(listen_addrs))
+        .route("/topics", get(topics))
+        .route("/dht/routing-table", get(routing_table))
+        .route("/dht/records/:key", get(get_record).put(put_record))
+        .route("/dht/providers/:key", get(get_providers).post(start_providing).delete(stop_providing))
+        .route("/dial", post(dial))
+        .route("/subscribe", post(subscribe))
+        .route("/unsubscribe", post(unsubscribe))
+        .route("/publish", post(publish))
+        .route("/disconnect/:peer", post(disconnect))
+        .with_state(node);
+
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind admin API on {}", addr))?;
+    info!("🛠️  Admin API listening on http://{}", listener.local_addr()?);
+
+    Ok(tokio::spawn(async move {
+        let stop = async {
+            let _ = stop.await;
+        };
+        if let Err(e) = axum::serve(listener, app).with_graceful_shutdown(stop).await {
+            error!("Admin API stopped: {}", e);
+        }
+    }))
+}
+
+fn bad_request(e: impl std::fmt::Display) -> ApiError {
+    ApiError(StatusCode::BAD_REQUEST, e.to_string())
+}
+
+async fn peers(State(node): State<NodeHandle>) -> ApiResult<Vec<PeerInfo>> {
+    Ok(Json(node.connected_peers().await?))
+}
+
+async fn listen_addrs(State(node): State<NodeHandle>) -> ApiResult<ListenAddrs> {
+    Ok(Json(node.listen_addrs().await?))
+}
+
+async fn topics(State(node): State<NodeHandle>) -> ApiResult<Vec<TopicInfo>> {
+    Ok(Json(node.topics().await?))
+}
+
+async fn routing_table(State(node): State<NodeHandle>) -> ApiResult<Vec<PeerInfo>> {
+    Ok(Json(node.routing_table().await?))
+}
+
+async fn get_record(
+    State(node): State<NodeHandle>,
+    Path(key): Path<String>,
+    Query(query): Query<GetRecordQuery>,
+) -> ApiResult<GetRecordOutcome> {
+    let quorum = parse_quorum(query.quorum.as_deref())?;
+    Ok(Json(node.get_record(key, quorum).await?))
+}
+
+async fn put_record(
+    State(node): State<NodeHandle>,
+    Path(key): Path<String>,
+    Json(body): Json<PutRecordRequest>,
+) -> ApiResult<PutRecordOutcome> {
+    let options = PutRecordOptions {
+        quorum: parse_quorum(body.quorum.as_deref())?,
+        ttl: body.ttl_secs.map(Duration::from_secs),
+        publisher: body.publisher.as_deref().map(str::parse::<PeerId>).transpose().map_err(bad_request)?,
+    };
+    Ok(Json(node.put_record(key, body.value, options).await?))
+}
+
+async fn get_providers(
+    State(node): State<NodeHandle>,
+    Path(key): Path<String>,
+) -> ApiResult<serde_json::Value> {
+    let providers = node.get_providers(key.clone()).await?;
+    Ok(Json(json!({ "key": key, "providers": providers })))
+}
+
+async fn start_providing(
+    State(node): State<NodeHandle>,
+    Path(key): Path<String>,
+) -> ApiResult<serde_json::Value> {
+    node.start_providing(key.clone()).await?;
+    Ok(Json(json!({ "providing": key })))
+}
+
+async fn stop_providing(
+    State(node): State<NodeHandle>,
+    Path(key): Path<String>,
+) -> ApiResult<serde_json::Value> {
+    node.stop_providing(key.clone()).await?;
+    Ok(Json(json!({ "stopped_providing": key })))
+}
+
+fn parse_quorum(quorum: Option<&str>) -> Result<RecordQuorum, ApiError> {
+    quorum.map_or(Ok(RecordQuorum::One), |q| q.parse().map_err(bad_request))
+}
+
+async fn dial(
+    State(node): State<NodeHandle>,
+    Json(body): Json<DialRequest>,
+) -> ApiResult<serde_json::Value> {
+    let addr: Multiaddr = body.addr.parse().map_err(bad_request)?;
+    node.dial(addr).await?;
+    Ok(Json(json!({ "dialing": body.addr })))
+}
+
+async fn subscribe(
+    State(node): State<NodeHandle>,
+    Json(body): Json<TopicRequest>,
+) -> ApiResult<serde_json::Value> {
+    let subscribed = node.subscribe(body.topic.clone()).await?;
+    Ok(Json(json!({ "topic": body.topic, "changed": subscribed })))
+}
+
+async fn unsubscribe(
+    State(node): State<NodeHandle>,
+    Json(body): Json<TopicRequest>,
+) -> ApiResult<serde_json::Value> {
+    let unsubscribed = node.unsubscribe(body.topic.clone()).await?;
+    Ok(Json(json!({ "topic": body.topic, "changed": unsubscribed })))
+}
+
+async fn publish(
+    State(node): State<NodeHandle>,
+    Json(body): Json<PublishRequest>,
+) -> ApiResult<serde_json::Value> {
+    let message_id = node.publish(body.topic.clone(), body.message).await?;
+    Ok(Json(json!({ "topic": body.topic, "message_id": message_id })))
+}
+
+async fn disconnect(
+    State(node): State<NodeHandle>,
+    Path(peer): Path<String>,
+) -> ApiResult<serde_json::Value> {
+    let peer_id: PeerId = peer
+        .parse()
+        .map_err(|e| bad_request(anyhow!("invalid peer ID {}: {}", peer, e)))?;
+    node.disconnect(peer_id).await?;
+    Ok(Json(json!({ "disconnected": peer_id })))
+}
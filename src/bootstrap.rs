@@ -0,0 +1,77 @@
+use anyhow::{anyhow, Context, Result};
+use libp2p::{multiaddr::Protocol, Multiaddr, PeerId};
+use std::{collections::HashMap, fs, path::Path};
+use tracing::{info, warn};
+
+/// Read bootstrap peers from a file with one multiaddr per line.
+///
+/// Blank lines and lines starting with `#` are ignored.
+pub fn load_bootstrap_file(path: &Path) -> Result<Vec<Multiaddr>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read bootstrap file {}", path.display()))?;
+
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            line.parse()
+                .map_err(|e| anyhow!("{}: invalid multiaddr '{}': {}", path.display(), line, e))
+        })
+        .collect()
+}
+
+/// Peer ID at the end of a bootstrap address. Validated by `NodeConfig`.
+pub(crate) fn peer_id_of(addr: &Multiaddr) -> Option<PeerId> {
+    match addr.iter().last() {
+        Some(Protocol::P2p(peer_id)) => Some(peer_id),
+        _ => None,
+    }
+}
+
+/// Outcome of dialing each bootstrap peer once, summarised when all dials finish.
+#[derive(Debug, Default)]
+pub(crate) struct BootstrapRound {
+    pending: HashMap<PeerId, Multiaddr>,
+    succeeded: Vec<(PeerId, Multiaddr)>,
+    failed: Vec<(PeerId, Multiaddr, String)>,
+}
+
+impl BootstrapRound {
+    pub(crate) fn dialing(&mut self, peer_id: PeerId, addr: Multiaddr) {
+        self.pending.insert(peer_id, addr);
+    }
+
+    pub(crate) fn succeeded(&mut self, peer_id: &PeerId) {
+        if let Some(addr) = self.pending.remove(peer_id) {
+            self.succeeded.push((*peer_id, addr));
+            self.finish_if_done();
+        }
+    }
+
+    pub(crate) fn failed(&mut self, peer_id: &PeerId, reason: impl ToString) {
+        if let Some(addr) = self.pending.remove(peer_id) {
+            self.failed.push((*peer_id, addr, reason.to_string()));
+            self.finish_if_done();
+        }
+    }
+
+    /// Log the summary once no dial is outstanding.
+    pub(crate) fn finish_if_done(&mut self) {
+        if !self.pending.is_empty() || (self.succeeded.is_empty() && self.failed.is_empty()) {
+            return;
+        }
+
+        let total = self.succeeded.len() + self.failed.len();
+        info!("🌐 Bootstrap summary: {}/{} peers reachable", self.succeeded.len(), total);
+        for (peer_id, addr) in &self.succeeded {
+            info!("   ✅ {} ({})", peer_id, addr);
+        }
+        for (peer_id, addr, reason) in &self.failed {
+            warn!("   ❌ {} ({}): {}", peer_id, addr, reason);
+        }
+
+        self.succeeded.clear();
+        self.failed.clear();
+    }
+}
@@ -0,0 +1,236 @@
+use anyhow::Result;
+use libp2p::{kad, Multiaddr, PeerId};
+use serde::Serialize;
+use std::{fmt, num::NonZeroUsize, str::FromStr, time::Duration};
+use tokio::sync::oneshot;
+
+use crate::handshake::HandshakeInfo;
+
+/// Requests handled inside the swarm loop of [`P2PNode::run`](crate::P2PNode::run),
+/// sent through a [`NodeHandle`](crate::NodeHandle).
+///
+/// Each command carries a oneshot sender for its reply so callers never touch
+/// the swarm directly and the loop never waits on them.
+#[derive(Debug)]
+pub(crate) enum Command {
+    ConnectedPeers {
+        reply: oneshot::Sender<Vec<PeerInfo>>,
+    },
+    ListenAddrs {
+        reply: oneshot::Sender<ListenAddrs>,
+    },
+    Topics {
+        reply: oneshot::Sender<Vec<TopicInfo>>,
+    },
+    RoutingTable {
+        reply: oneshot::Sender<Vec<PeerInfo>>,
+    },
+    Dial {
+        addr: Multiaddr,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    Publish {
+        topic: String,
+        data: Vec<u8>,
+        reply: oneshot::Sender<Result<String>>,
+    },
+    Subscribe {
+        topic: String,
+        reply: oneshot::Sender<Result<bool>>,
+    },
+    Unsubscribe {
+        topic: String,
+        reply: oneshot::Sender<Result<bool>>,
+    },
+    Disconnect {
+        peer_id: PeerId,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    GetClosestPeers {
+        key: PeerId,
+        reply: oneshot::Sender<Result<Vec<PeerId>>>,
+    },
+    PutRecord {
+        key: Vec<u8>,
+        value: Vec<u8>,
+        options: PutRecordOptions,
+        reply: oneshot::Sender<Result<PutRecordOutcome>>,
+    },
+    GetRecord {
+        key: Vec<u8>,
+        quorum: RecordQuorum,
+        reply: oneshot::Sender<Result<GetRecordOutcome>>,
+    },
+    StartProviding {
+        key: Vec<u8>,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    StopProviding {
+        key: Vec<u8>,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    GetProviders {
+        key: Vec<u8>,
+        reply: oneshot::Sender<Result<Vec<PeerId>>>,
+    },
+    Shutdown {
+        reply: oneshot::Sender<()>,
+    },
+}
+
+/// Reply owed for a Kademlia query started by a [`Command`].
+#[derive(Debug)]
+pub(crate) enum PendingQuery {
+    ClosestPeers(oneshot::Sender<Result<Vec<PeerId>>>),
+    PutRecord(oneshot::Sender<Result<PutRecordOutcome>>),
+    /// Closest-peer lookup for a record published on behalf of another peer.
+    PutRecordAs {
+        reply: oneshot::Sender<Result<PutRecordOutcome>>,
+        record: kad::Record,
+        quorum: RecordQuorum,
+    },
+    /// Sending that record to the `peers` the lookup found.
+    PutRecordTo {
+        reply: oneshot::Sender<Result<PutRecordOutcome>>,
+        peers: Vec<PeerId>,
+        quorum: usize,
+    },
+    /// Records are collected across progress steps until `quorum` peers answered.
+    GetRecord {
+        reply: oneshot::Sender<Result<GetRecordOutcome>>,
+        quorum: usize,
+        records: Vec<FoundRecord>,
+    },
+    StartProviding(oneshot::Sender<Result<()>>),
+    /// Providers are collected across progress steps until the query ends.
+    GetProviders {
+        reply: oneshot::Sender<Result<Vec<PeerId>>>,
+        providers: Vec<PeerId>,
+    },
+}
+
+/// A peer together with the addresses we know for it.
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerInfo {
+    pub peer_id: PeerId,
+    pub addresses: Vec<Multiaddr>,
+    /// What the peer told us through the handshake protocol, if it completed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub handshake: Option<HandshakeInfo>,
+}
+
+/// Addresses the node listens on and the external addresses it announces.
+#[derive(Debug, Clone, Serialize)]
+pub struct ListenAddrs {
+    pub listen: Vec<Multiaddr>,
+    pub external: Vec<Multiaddr>,
+}
+
+/// A subscribed gossipsub topic and the number of peers in its mesh.
+#[derive(Debug, Clone, Serialize)]
+pub struct TopicInfo {
+    pub topic: String,
+    pub mesh_peers: usize,
+}
+
+/// How many peers must store, or answer with, a DHT record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RecordQuorum {
+    #[default]
+    One,
+    /// More than half of the closest peers to the key.
+    Majority,
+    /// All of the closest peers to the key.
+    All,
+    N(NonZeroUsize),
+}
+
+impl RecordQuorum {
+    pub(crate) fn as_kad_quorum(self) -> kad::Quorum {
+        match self {
+            RecordQuorum::One => kad::Quorum::One,
+            RecordQuorum::Majority => kad::Quorum::Majority,
+            RecordQuorum::All => kad::Quorum::All,
+            RecordQuorum::N(n) => kad::Quorum::N(n),
+        }
+    }
+
+    /// Number of answers needed out of the `replication_factor` closest peers.
+    pub(crate) fn required(self, replication_factor: usize) -> usize {
+        match self {
+            RecordQuorum::One => 1,
+            RecordQuorum::Majority => replication_factor / 2 + 1,
+            RecordQuorum::All => replication_factor,
+            RecordQuorum::N(n) => n.get().min(replication_factor),
+        }
+    }
+}
+
+impl fmt::Display for RecordQuorum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordQuorum::One => write!(f, "one"),
+            RecordQuorum::Majority => write!(f, "majority"),
+            RecordQuorum::All => write!(f, "all"),
+            RecordQuorum::N(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+impl FromStr for RecordQuorum {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "one" => Ok(RecordQuorum::One),
+            "majority" => Ok(RecordQuorum::Majority),
+            "all" => Ok(RecordQuorum::All),
+            other => other
+                .parse::<NonZeroUsize>()
+                .map(RecordQuorum::N)
+                .map_err(|_| format!("invalid quorum '{}' (expected one, majority, all or a positive number)", other)),
+        }
+    }
+}
+
+/// Options for [`NodeHandle::put_record`](crate::NodeHandle::put_record).
+#[derive(Debug, Clone, Default)]
+pub struct PutRecordOptions {
+    /// Peers that must store the record for the put to succeed.
+    pub quorum: RecordQuorum,
+    /// How long the record lives; the configured record TTL when unset.
+    pub ttl: Option<Duration>,
+    /// Peer the record is published on behalf of; our own peer ID when unset.
+    pub publisher: Option<PeerId>,
+}
+
+/// Result of storing a DHT record. The record is always kept in our own
+/// store under the requested publisher, even when the quorum fails.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum PutRecordOutcome {
+    Stored,
+    QuorumFailed { stored_at: Vec<PeerId>, quorum: usize },
+}
+
+/// Result of a DHT lookup.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum GetRecordOutcome {
+    /// At least the requested number of peers returned the record.
+    Found { records: Vec<FoundRecord> },
+    NotFound,
+    /// Fewer peers than the quorum returned the record.
+    QuorumFailed { records: Vec<FoundRecord>, quorum: usize },
+}
+
+/// A DHT record together with where it came from.
+#[derive(Debug, Clone, Serialize)]
+pub struct FoundRecord {
+    pub value: Vec<u8>,
+    pub publisher: Option<PeerId>,
+    /// Peer that returned the record, `None` when it came from our own store.
+    pub peer: Option<PeerId>,
+    /// Seconds until the record expires, `None` if it does not.
+    pub expires_in_secs: Option<u64>,
+}
@@ -0,0 +1,737 @@
+use libp2p::{
+    gossipsub::{PeerScoreParams, PeerScoreThresholds, TopicScoreParams},
+    identity::Keypair,
+    kad,
+    multiaddr::Protocol,
+    Multiaddr,
+};
+use std::{collections::HashMap, fmt, net::SocketAddr, path::PathBuf, str::FromStr, sync::Arc, time::Duration};
+
+use crate::validation::MessageValidator;
+
+pub const DEFAULT_PROTOCOL_VERSION: &str = "/node-eeb/1.0.0";
+pub const DEFAULT_HANDSHAKE_TOPIC: &str = "node-eeb-handshakes";
+pub const DEFAULT_HANDSHAKE_INTERVAL: Duration = Duration::from_secs(30);
+pub const DEFAULT_HANDSHAKE_MAX_CLOCK_SKEW: Duration = Duration::from_secs(300);
+pub const DEFAULT_BOOTSTRAP_INTERVAL: Duration = Duration::from_secs(300);
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+pub const DEFAULT_LOG_FILTER: &str = "info,libp2p=debug";
+pub const DEFAULT_ADMIN_ADDR: &str = "127.0.0.1:4080";
+pub const DEFAULT_METRICS_ADDR: &str = "127.0.0.1:9090";
+
+/// Public IPFS bootstrap peers, used when no bootstrap peers are configured.
+pub const IPFS_BOOTSTRAP_NODES: &[&str] = &[
+    "/dnsaddr/bootstrap.libp2p.io/p2p/QmNnooDu7bfjPFoTZYxMNLWUQJyrVwtbZg5gBMjTezGAJN",
+    "/dnsaddr/bootstrap.libp2p.io/p2p/QmQCU2EcMqAqQPR2i9bChDtGNJchTbq5TbXJJ16u19uLTa",
+    "/dnsaddr/bootstrap.libp2p.io/p2p/QmbLHAnMoJPWSCR5Zp9Kky4f5RmvJw2e6GrmNw9hxKL1MH",
+];
+
+/// Errors returned when a [`NodeConfig`] is inconsistent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// Both a keyfile and an in-memory keypair were supplied.
+    ConflictingIdentity,
+    /// An address that must identify a peer does not end in `/p2p/<peer-id>`.
+    MissingPeerId {
+        option: &'static str,
+        addr: Multiaddr,
+    },
+    /// An interval was set to zero.
+    ZeroInterval(&'static str),
+    /// A required string option was empty.
+    Empty(&'static str),
+    /// Gossipsub mesh bounds must satisfy `mesh_n_low <= mesh_n <= mesh_n_high`.
+    InvalidMeshBounds {
+        mesh_n_low: usize,
+        mesh_n: usize,
+        mesh_n_high: usize,
+    },
+    /// Gossipsub peer score parameters or thresholds are out of range.
+    InvalidPeerScoring(String),
+    /// Records would expire before their publisher republishes them.
+    RepublishAfterExpiry {
+        republish_interval: Duration,
+        record_ttl: Duration,
+    },
+    /// Provider records would expire before we announce them again.
+    ReprovideAfterExpiry {
+        reprovide_interval: Duration,
+        provider_ttl: Duration,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::ConflictingIdentity => {
+                write!(f, "an identity file and a keypair cannot both be set")
+            }
+            ConfigError::MissingPeerId { option, addr } => {
+                write!(f, "{} address {} must end in /p2p/<peer-id>", option, addr)
+            }
+            ConfigError::ZeroInterval(name) => write!(f, "{} must be greater than zero", name),
+            ConfigError::Empty(name) => write!(f, "{} must not be empty", name),
+            ConfigError::InvalidMeshBounds { mesh_n_low, mesh_n, mesh_n_high } => write!(
+                f,
+                "gossipsub mesh bounds must satisfy mesh_n_low ({}) <= mesh_n ({}) <= mesh_n_high ({})",
+                mesh_n_low, mesh_n, mesh_n_high
+            ),
+            ConfigError::InvalidPeerScoring(reason) => write!(f, "invalid gossipsub peer scoring: {}", reason),
+            ConfigError::RepublishAfterExpiry { republish_interval, record_ttl } => write!(
+                f,
+                "DHT republish interval ({}s) must be shorter than the record TTL ({}s)",
+                republish_interval.as_secs(),
+                record_ttl.as_secs()
+            ),
+            ConfigError::ReprovideAfterExpiry { reprovide_interval, provider_ttl } => write!(
+                f,
+                "DHT reprovide interval ({}s) must be shorter than the provider record TTL ({}s)",
+                reprovide_interval.as_secs(),
+                provider_ttl.as_secs()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Kademlia operating mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KademliaMode {
+    /// Answer inbound DHT queries.
+    Server,
+    /// Only issue queries, never answer them.
+    Client,
+    /// Let libp2p pick based on confirmed external addresses.
+    Auto,
+}
+
+impl KademliaMode {
+    pub(crate) fn as_kad_mode(self) -> Option<kad::Mode> {
+        match self {
+            KademliaMode::Server => Some(kad::Mode::Server),
+            KademliaMode::Client => Some(kad::Mode::Client),
+            KademliaMode::Auto => None,
+        }
+    }
+}
+
+impl fmt::Display for KademliaMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KademliaMode::Server => write!(f, "server"),
+            KademliaMode::Client => write!(f, "client"),
+            KademliaMode::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+impl FromStr for KademliaMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "server" => Ok(KademliaMode::Server),
+            "client" => Ok(KademliaMode::Client),
+            "auto" => Ok(KademliaMode::Auto),
+            other => Err(format!("unknown Kademlia mode '{}' (expected server, client or auto)", other)),
+        }
+    }
+}
+
+/// How gossipsub message IDs, which messages are deduplicated by, are derived.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageIdMode {
+    /// SHA-256 over publisher, sequence number and payload, so every publish
+    /// is delivered even when the payload repeats.
+    Publisher,
+    /// SHA-256 over topic and payload, so identical payloads on a topic are
+    /// delivered once no matter who published them.
+    Content,
+}
+
+impl fmt::Display for MessageIdMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MessageIdMode::Publisher => write!(f, "publisher"),
+            MessageIdMode::Content => write!(f, "content"),
+        }
+    }
+}
+
+impl FromStr for MessageIdMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "publisher" => Ok(MessageIdMode::Publisher),
+            "content" => Ok(MessageIdMode::Content),
+            other => Err(format!("unknown message ID mode '{}' (expected publisher or content)", other)),
+        }
+    }
+}
+
+/// Gossipsub tuning parameters.
+#[derive(Debug, Clone)]
+pub struct GossipsubSettings {
+    pub heartbeat_interval: Duration,
+    pub mesh_n: usize,
+    pub mesh_n_low: usize,
+    pub mesh_n_high: usize,
+    pub max_transmit_size: usize,
+    /// Message ID mode for every topic not listed in `content_addressed_topics`.
+    pub message_id_mode: MessageIdMode,
+    /// Topics whose messages are identified by content alone, whatever `message_id_mode` says.
+    pub content_addressed_topics: Vec<String>,
+}
+
+impl Default for GossipsubSettings {
+    fn default() -> Self {
+        Self {
+            heartbeat_interval: Duration::from_secs(10),
+            mesh_n: 6,
+            mesh_n_low: 5,
+            mesh_n_high: 12,
+            max_transmit_size: 65536,
+            message_id_mode: MessageIdMode::Publisher,
+            content_addressed_topics: Vec::new(),
+        }
+    }
+}
+
+/// Kademlia record storage, limits and expiry.
+#[derive(Debug, Clone)]
+pub struct RecordStoreSettings {
+    /// File the records are saved to so they survive restarts. Records are
+    /// only kept in memory when unset.
+    pub path: Option<PathBuf>,
+    pub max_records: usize,
+    /// Largest record value accepted, in bytes.
+    pub max_value_bytes: usize,
+    /// Provider records kept per key.
+    pub max_providers_per_key: usize,
+    /// How long stored records live unless republished.
+    pub record_ttl: Duration,
+    /// How often records we published are published again.
+    pub republish_interval: Duration,
+    /// How long provider records live unless the provider announces them again.
+    pub provider_ttl: Duration,
+    /// How often keys we provide are announced again.
+    pub reprovide_interval: Duration,
+}
+
+impl Default for RecordStoreSettings {
+    fn default() -> Self {
+        Self {
+            path: None,
+            max_records: 1024,
+            max_value_bytes: 65 * 1024,
+            max_providers_per_key: 20,
+            record_ttl: Duration::from_secs(36 * 60 * 60),
+            republish_interval: Duration::from_secs(22 * 60 * 60),
+            provider_ttl: Duration::from_secs(24 * 60 * 60),
+            reprovide_interval: Duration::from_secs(12 * 60 * 60),
+        }
+    }
+}
+
+/// Gossipsub v1.1 peer scoring.
+///
+/// Peers whose score drops below `thresholds.graylist_threshold` have all
+/// their gossipsub traffic ignored until the score decays back up.
+#[derive(Debug, Clone, Default)]
+pub struct PeerScoring {
+    pub params: PeerScoreParams,
+    pub thresholds: PeerScoreThresholds,
+    /// Per-topic parameters by topic name. The handshake topic falls back to
+    /// [`handshake_topic_score_params`] when it has no entry here.
+    pub topics: HashMap<String, TopicScoreParams>,
+}
+
+/// Score parameters for the handshake topic.
+///
+/// Handshakes are too sparse for mesh delivery penalties to be fair, so only
+/// first deliveries are rewarded and invalid handshakes punished. The penalty
+/// grows with the square of the count, so three rejected handshakes in quick
+/// succession push a peer past the default graylist threshold of -80.
+pub fn handshake_topic_score_params() -> TopicScoreParams {
+    TopicScoreParams {
+        topic_weight: 1.0,
+        time_in_mesh_weight: 0.01,
+        time_in_mesh_quantum: Duration::from_secs(1),
+        time_in_mesh_cap: 3600.0,
+        first_message_deliveries_weight: 1.0,
+        first_message_deliveries_decay: 0.5,
+        first_message_deliveries_cap: 10.0,
+        mesh_message_deliveries_weight: 0.0,
+        mesh_failure_penalty_weight: 0.0,
+        invalid_message_deliveries_weight: -20.0,
+        invalid_message_deliveries_decay: 0.99,
+        ..TopicScoreParams::default()
+    }
+}
+
+/// Configuration for a [`P2PNode`](crate::P2PNode), created through [`NodeConfig::builder`].
+#[derive(Debug, Clone)]
+pub struct NodeConfig {
+    pub(crate) node_name: Option<String>,
+    pub(crate) listen_addrs: Vec<Multiaddr>,
+    pub(crate) external_addrs: Vec<Multiaddr>,
+    pub(crate) websocket_port: Option<u16>,
+    pub(crate) identity_file: Option<PathBuf>,
+    pub(crate) keypair: Option<Keypair>,
+    pub(crate) swarm_key_file: Option<PathBuf>,
+    pub(crate) peer_store_file: Option<PathBuf>,
+    pub(crate) gossipsub: GossipsubSettings,
+    pub(crate) peer_scoring: Option<PeerScoring>,
+    pub(crate) enable_dht: bool,
+    pub(crate) record_store: RecordStoreSettings,
+    pub(crate) kademlia_mode: KademliaMode,
+    pub(crate) enable_mdns: bool,
+    pub(crate) use_bootstrap: bool,
+    pub(crate) bootstrap_peers: Vec<Multiaddr>,
+    pub(crate) relay_server: bool,
+    pub(crate) relay_via: Vec<Multiaddr>,
+    pub(crate) connect: Vec<Multiaddr>,
+    pub(crate) protocol_version: String,
+    pub(crate) handshake_topic: String,
+    pub(crate) topics: Vec<String>,
+    pub(crate) provided_keys: Vec<String>,
+    pub(crate) dial_providers: bool,
+    pub(crate) validators: HashMap<String, Arc<dyn MessageValidator>>,
+    pub(crate) handshake_interval: Duration,
+    pub(crate) handshake_max_clock_skew: Duration,
+    pub(crate) bootstrap_interval: Duration,
+    pub(crate) shutdown_timeout: Duration,
+    pub(crate) log_filter: String,
+    pub(crate) admin_listen: Option<SocketAddr>,
+    pub(crate) metrics_listen: Option<SocketAddr>,
+}
+
+impl Default for NodeConfig {
+    fn default() -> Self {
+        Self {
+            node_name: None,
+            listen_addrs: default_listen_addrs(0),
+            external_addrs: Vec::new(),
+            websocket_port: None,
+            identity_file: None,
+            keypair: None,
+            swarm_key_file: None,
+            peer_store_file: None,
+            gossipsub: GossipsubSettings::default(),
+            peer_scoring: Some(PeerScoring::default()),
+            enable_dht: true,
+            record_store: RecordStoreSettings::default(),
+            kademlia_mode: KademliaMode::Server,
+            enable_mdns: true,
+            use_bootstrap: true,
+            bootstrap_peers: IPFS_BOOTSTRAP_NODES
+                .iter()
+                .map(|addr| addr.parse().expect("valid bootstrap multiaddr"))
+                .collect(),
+            relay_server: false,
+            relay_via: Vec::new(),
+            connect: Vec::new(),
+            protocol_version: DEFAULT_PROTOCOL_VERSION.to_string(),
+            handshake_topic: DEFAULT_HANDSHAKE_TOPIC.to_string(),
+            topics: Vec::new(),
+            provided_keys: Vec::new(),
+            dial_providers: true,
+            validators: HashMap::new(),
+            handshake_interval: DEFAULT_HANDSHAKE_INTERVAL,
+            handshake_max_clock_skew: DEFAULT_HANDSHAKE_MAX_CLOCK_SKEW,
+            bootstrap_interval: DEFAULT_BOOTSTRAP_INTERVAL,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            log_filter: DEFAULT_LOG_FILTER.to_string(),
+            admin_listen: None,
+            metrics_listen: None,
+        }
+    }
+}
+
+impl NodeConfig {
+    pub fn builder() -> NodeConfigBuilder {
+        NodeConfigBuilder::default()
+    }
+
+    /// Tracing filter directive the binary installs for this node.
+    pub fn log_filter(&self) -> &str {
+        &self.log_filter
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.identity_file.is_some() && self.keypair.is_some() {
+            return Err(ConfigError::ConflictingIdentity);
+        }
+
+        require_peer_ids("bootstrap", &self.bootstrap_peers)?;
+        require_peer_ids("relay_via", &self.relay_via)?;
+        require_peer_ids("connect", &self.connect)?;
+
+        if self.protocol_version.is_empty() {
+            return Err(ConfigError::Empty("protocol_version"));
+        }
+        if self.handshake_topic.is_empty() {
+            return Err(ConfigError::Empty("handshake_topic"));
+        }
+        if self.topics.iter().any(String::is_empty) {
+            return Err(ConfigError::Empty("topic"));
+        }
+        if self.gossipsub.content_addressed_topics.iter().any(String::is_empty) {
+            return Err(ConfigError::Empty("content-addressed topic"));
+        }
+        if self.provided_keys.iter().any(String::is_empty) {
+            return Err(ConfigError::Empty("provided key"));
+        }
+
+        if self.handshake_interval.is_zero() {
+            return Err(ConfigError::ZeroInterval("handshake_interval"));
+        }
+        if self.bootstrap_interval.is_zero() {
+            return Err(ConfigError::ZeroInterval("bootstrap_interval"));
+        }
+        if self.record_store.record_ttl.is_zero() {
+            return Err(ConfigError::ZeroInterval("record_ttl"));
+        }
+        if self.record_store.republish_interval.is_zero() {
+            return Err(ConfigError::ZeroInterval("record republish_interval"));
+        }
+        if self.record_store.provider_ttl.is_zero() {
+            return Err(ConfigError::ZeroInterval("provider_ttl"));
+        }
+        if self.record_store.reprovide_interval.is_zero() {
+            return Err(ConfigError::ZeroInterval("reprovide_interval"));
+        }
+        if self.gossipsub.heartbeat_interval.is_zero() {
+            return Err(ConfigError::ZeroInterval("gossipsub heartbeat_interval"));
+        }
+
+        let GossipsubSettings { mesh_n, mesh_n_low, mesh_n_high, .. } = self.gossipsub;
+        if !(mesh_n_low <= mesh_n && mesh_n <= mesh_n_high) {
+            return Err(ConfigError::InvalidMeshBounds { mesh_n_low, mesh_n, mesh_n_high });
+        }
+
+        let RecordStoreSettings { republish_interval, record_ttl, reprovide_interval, provider_ttl, .. } =
+            self.record_store;
+        if republish_interval >= record_ttl {
+            return Err(ConfigError::RepublishAfterExpiry { republish_interval, record_ttl });
+        }
+        if reprovide_interval >= provider_ttl {
+            return Err(ConfigError::ReprovideAfterExpiry { reprovide_interval, provider_ttl });
+        }
+
+        if let Some(scoring) = &self.peer_scoring {
+            scoring
+                .thresholds
+                .validate()
+                .map_err(|e| ConfigError::InvalidPeerScoring(e.to_string()))?;
+            scoring.params.validate().map_err(ConfigError::InvalidPeerScoring)?;
+            for (topic, params) in &scoring.topics {
+                params
+                    .validate()
+                    .map_err(|e| ConfigError::InvalidPeerScoring(format!("topic {}: {}", topic, e)))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// WebSocket listeners on all IPv4 and IPv6 interfaces for `port`.
+pub fn websocket_listen_addrs(port: u16) -> Vec<Multiaddr> {
+    [
+        format!("/ip4/0.0.0.0/tcp/{}/ws", port),
+        format!("/ip6/::/tcp/{}/ws", port),
+    ]
+    .iter()
+    .map(|addr| addr.parse().expect("valid websocket multiaddr"))
+    .collect()
+}
+
+/// TCP and QUIC listeners on all IPv4 and IPv6 interfaces for `port` (0 picks a random port).
+pub fn default_listen_addrs(port: u16) -> Vec<Multiaddr> {
+    [
+        format!("/ip4/0.0.0.0/tcp/{}", port),
+        format!("/ip4/0.0.0.0/udp/{}/quic-v1", port),
+        format!("/ip6/::/tcp/{}", port),
+        format!("/ip6/::/udp/{}/quic-v1", port),
+    ]
+    .iter()
+    .map(|addr| addr.parse().expect("valid listen multiaddr"))
+    .collect()
+}
+
+fn require_peer_ids(option: &'static str, addrs: &[Multiaddr]) -> Result<(), ConfigError> {
+    for addr in addrs {
+        if !matches!(addr.iter().last(), Some(Protocol::P2p(_))) {
+            return Err(ConfigError::MissingPeerId { option, addr: addr.clone() });
+        }
+    }
+    Ok(())
+}
+
+/// Builder for [`NodeConfig`]. Unset options keep their defaults.
+#[derive(Debug, Clone, Default)]
+pub struct NodeConfigBuilder {
+    config: NodeConfig,
+    custom_listen_addrs: bool,
+}
+
+impl NodeConfigBuilder {
+    /// The configuration as built so far, before validation.
+    pub(crate) fn current(&self) -> &NodeConfig {
+        &self.config
+    }
+
+    pub fn node_name(mut self, name: impl Into<String>) -> Self {
+        self.config.node_name = Some(name.into());
+        self
+    }
+
+    /// Add an address to listen on. The first call replaces the default
+    /// [`default_listen_addrs`] listeners.
+    pub fn listen_addr(mut self, addr: Multiaddr) -> Self {
+        if !self.custom_listen_addrs {
+            self.config.listen_addrs.clear();
+            self.custom_listen_addrs = true;
+        }
+        self.config.listen_addrs.push(addr);
+        self
+    }
+
+    /// Replace all listen addresses, including any added with [`Self::listen_addr`].
+    pub fn listen_addrs(mut self, addrs: Vec<Multiaddr>) -> Self {
+        self.config.listen_addrs = addrs;
+        self.custom_listen_addrs = true;
+        self
+    }
+
+    /// Also accept WebSocket connections on `port` (`/tcp/<port>/ws`), e.g. 443
+    /// behind a firewall that only allows HTTPS. Dialing `/ws` addresses works
+    /// regardless of this setting.
+    pub fn websocket_port(mut self, port: Option<u16>) -> Self {
+        self.config.websocket_port = port;
+        self
+    }
+
+    /// Publicly reachable addresses to announce, e.g. the VPS address of a
+    /// relay server. Other addresses are only announced once AutoNAT confirms them.
+    pub fn external_addrs(mut self, addrs: Vec<Multiaddr>) -> Self {
+        self.config.external_addrs = addrs;
+        self
+    }
+
+    /// Load the identity from a keyfile, generating it if missing.
+    pub fn identity_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.identity_file = Some(path.into());
+        self
+    }
+
+    /// Join a private network using the pre-shared key in `path`
+    /// (`/key/swarm/psk/1.0.0/` format). The public IPFS bootstrap peers are
+    /// dropped from the bootstrap list, even when listed explicitly.
+    pub fn swarm_key_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.swarm_key_file = Some(path.into());
+        self
+    }
+
+    /// Remember peers in the JSON file at `path` and redial them on startup,
+    /// before the bootstrap peers.
+    pub fn peer_store(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.peer_store_file = Some(path.into());
+        self
+    }
+
+    /// Use an in-memory keypair as the node identity.
+    pub fn keypair(mut self, keypair: Keypair) -> Self {
+        self.config.keypair = Some(keypair);
+        self
+    }
+
+    pub fn gossipsub(mut self, settings: GossipsubSettings) -> Self {
+        self.config.gossipsub = settings;
+        self
+    }
+
+    /// Gossipsub peer scoring, enabled with libp2p's default parameters
+    /// unless set here. `None` disables scoring and graylisting entirely.
+    pub fn peer_scoring(mut self, scoring: Option<PeerScoring>) -> Self {
+        self.config.peer_scoring = scoring;
+        self
+    }
+
+    pub fn enable_dht(mut self, enable: bool) -> Self {
+        self.config.enable_dht = enable;
+        self
+    }
+
+    /// Where and how many Kademlia records are stored, and when they expire.
+    pub fn record_store(mut self, settings: RecordStoreSettings) -> Self {
+        self.config.record_store = settings;
+        self
+    }
+
+    /// Save Kademlia records to the file at `path` so they survive restarts.
+    /// Shorthand for setting [`RecordStoreSettings::path`].
+    pub fn dht_store(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.record_store.path = Some(path.into());
+        self
+    }
+
+    pub fn kademlia_mode(mut self, mode: KademliaMode) -> Self {
+        self.config.kademlia_mode = mode;
+        self
+    }
+
+    pub fn enable_mdns(mut self, enable: bool) -> Self {
+        self.config.enable_mdns = enable;
+        self
+    }
+
+    pub fn use_bootstrap(mut self, enable: bool) -> Self {
+        self.config.use_bootstrap = enable;
+        self
+    }
+
+    /// Replace the bootstrap peer list (the public IPFS peers by default).
+    /// Each address must end in `/p2p/<peer-id>`; an empty list bootstraps from nothing.
+    pub fn bootstrap_peers(mut self, peers: Vec<Multiaddr>) -> Self {
+        self.config.bootstrap_peers = peers;
+        self
+    }
+
+    pub fn relay_server(mut self, enable: bool) -> Self {
+        self.config.relay_server = enable;
+        self
+    }
+
+    /// Reserve a slot on each relay (addresses ending in `/p2p/<relay-id>`) and
+    /// listen on its `/p2p-circuit` address so NATed nodes stay reachable.
+    pub fn relay_via(mut self, relays: Vec<Multiaddr>) -> Self {
+        self.config.relay_via = relays;
+        self
+    }
+
+    /// Peers to dial on startup, each ending in `/p2p/<peer-id>`.
+    pub fn connect(mut self, peers: Vec<Multiaddr>) -> Self {
+        self.config.connect = peers;
+        self
+    }
+
+    /// Protocol version advertised through identify.
+    pub fn protocol_version(mut self, version: impl Into<String>) -> Self {
+        self.config.protocol_version = version.into();
+        self
+    }
+
+    /// Gossipsub topic used for handshake announcements.
+    pub fn handshake_topic(mut self, topic: impl Into<String>) -> Self {
+        self.config.handshake_topic = topic.into();
+        self
+    }
+
+    /// Application topics to subscribe to at startup. More can be joined at
+    /// runtime through [`NodeHandle::subscribe`](crate::NodeHandle::subscribe).
+    pub fn topics(mut self, topics: Vec<String>) -> Self {
+        self.config.topics = topics;
+        self
+    }
+
+    /// DHT keys to announce ourselves as a provider of once we have peers,
+    /// and again every [`RecordStoreSettings::reprovide_interval`]. More can be
+    /// announced at runtime through
+    /// [`NodeHandle::start_providing`](crate::NodeHandle::start_providing).
+    pub fn provide(mut self, keys: Vec<String>) -> Self {
+        self.config.provided_keys = keys;
+        self
+    }
+
+    /// Dial providers found by [`NodeHandle::get_providers`](crate::NodeHandle::get_providers)
+    /// that we are not connected to yet. Enabled by default.
+    pub fn dial_providers(mut self, enable: bool) -> Self {
+        self.config.dial_providers = enable;
+        self
+    }
+
+    /// Validate every message on `topic` with `validator` before it is
+    /// delivered or forwarded. Replaces the built-in
+    /// [`HandshakeValidator`](crate::validation::HandshakeValidator) when
+    /// `topic` is the handshake topic.
+    pub fn validator(mut self, topic: impl Into<String>, validator: impl MessageValidator + 'static) -> Self {
+        self.config.validators.insert(topic.into(), Arc::new(validator));
+        self
+    }
+
+    /// How far a handshake timestamp may be from our clock before the
+    /// handshake is rejected.
+    pub fn handshake_max_clock_skew(mut self, skew: Duration) -> Self {
+        self.config.handshake_max_clock_skew = skew;
+        self
+    }
+
+    pub fn handshake_interval(mut self, interval: Duration) -> Self {
+        self.config.handshake_interval = interval;
+        self
+    }
+
+    pub fn bootstrap_interval(mut self, interval: Duration) -> Self {
+        self.config.bootstrap_interval = interval;
+        self
+    }
+
+    /// Upper bound on how long a graceful shutdown may take before
+    /// remaining connections are dropped.
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.config.shutdown_timeout = timeout;
+        self
+    }
+
+    pub fn log_filter(mut self, filter: impl Into<String>) -> Self {
+        self.config.log_filter = filter.into();
+        self
+    }
+
+    /// Serve the HTTP admin API on `addr`. Keep it on localhost: the API
+    /// has no authentication and can dial, publish and disconnect peers.
+    pub fn admin_api(mut self, addr: Option<SocketAddr>) -> Self {
+        self.config.admin_listen = addr;
+        self
+    }
+
+    /// Serve Prometheus metrics at `http://<addr>/metrics`.
+    pub fn metrics(mut self, addr: Option<SocketAddr>) -> Self {
+        self.config.metrics_listen = addr;
+        self
+    }
+
+    /// Turn the configuration into a short-lived client that can run next
+    /// to a node started from the same settings: a fresh keypair, random
+    /// listen ports, and no peer store, DHT store, provided keys, admin API
+    /// or metrics. Call it after all other options.
+    pub fn ephemeral(mut self) -> Self {
+        self = self.listen_addrs(default_listen_addrs(0));
+        self.config.websocket_port = None;
+        self.config.external_addrs.clear();
+        self.config.identity_file = None;
+        self.config.keypair = Some(Keypair::generate_ed25519());
+        self.config.peer_store_file = None;
+        self.config.record_store.path = None;
+        self.config.provided_keys.clear();
+        self.config.admin_listen = None;
+        self.config.metrics_listen = None;
+        self
+    }
+
+    pub fn build(mut self) -> Result<NodeConfig, ConfigError> {
+        // A private network can never reach the public IPFS peers, so never dial them
+        if self.config.swarm_key_file.is_some() {
+            self.config
+                .bootstrap_peers
+                .retain(|addr| !IPFS_BOOTSTRAP_NODES.contains(&addr.to_string().as_str()));
+        }
+
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
@@ -0,0 +1,436 @@
+use anyhow::{anyhow, Context, Result};
+use libp2p::Multiaddr;
+use serde::{Deserialize, Serialize};
+use std::{fs, net::SocketAddr, path::Path, path::PathBuf, time::Duration};
+
+use crate::bootstrap::load_bootstrap_file;
+use crate::config::{GossipsubSettings, KademliaMode, MessageIdMode, NodeConfig, NodeConfigBuilder};
+
+/// On-disk TOML representation of a [`NodeConfig`].
+///
+/// Every field is optional; anything left out keeps the value already set on
+/// the builder it is applied to.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    pub node: NodeSection,
+    pub gossipsub: GossipsubSection,
+    pub dht: DhtSection,
+    pub mdns: MdnsSection,
+    pub relay: RelaySection,
+    pub handshake: HandshakeSection,
+    pub admin: AdminSection,
+    pub metrics: MetricsSection,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NodeSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identity: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub swarm_key: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peer_store: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connect: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_addrs: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub websocket_port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shutdown_timeout_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log: Option<String>,
+    /// Run the binary's console on stdin. Not part of [`NodeConfig`], so
+    /// [`ConfigFile::apply`] leaves it to the binary.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interactive: Option<bool>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GossipsubSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heartbeat_interval_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mesh_n: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mesh_n_low: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mesh_n_high: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_transmit_size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_addressed_topics: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topics: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peer_scoring: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gossip_threshold: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub publish_threshold: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub graylist_threshold: Option<f64>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DhtSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bootstrap: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bootstrap_peers: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bootstrap_file: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bootstrap_interval_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record_store: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_records: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_record_size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_providers_per_key: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record_ttl_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub republish_interval_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider_ttl_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reprovide_interval_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provide: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dial_providers: Option<bool>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MdnsSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RelaySection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub via: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HandshakeSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_clock_skew_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen: Option<SocketAddr>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen: Option<SocketAddr>,
+}
+
+impl ConfigFile {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))
+    }
+
+    /// Apply every option present in the file on top of `builder`.
+    pub fn apply(self, mut builder: NodeConfigBuilder) -> Result<NodeConfigBuilder> {
+        let node = self.node;
+        if let Some(name) = node.name {
+            builder = builder.node_name(name);
+        }
+        if let Some(path) = node.identity {
+            builder = builder.identity_file(path);
+        }
+        if let Some(path) = node.swarm_key {
+            builder = builder.swarm_key_file(path);
+        }
+        if let Some(path) = node.peer_store {
+            builder = builder.peer_store(path);
+        }
+        if let Some(connect) = node.connect {
+            builder = builder.connect(parse_addrs(&connect, "node.connect")?);
+        }
+        if let Some(listen) = node.listen {
+            builder = builder.listen_addrs(parse_addrs(&listen, "node.listen")?);
+        }
+        if let Some(port) = node.websocket_port {
+            builder = builder.websocket_port(Some(port));
+        }
+        if let Some(external) = node.external_addrs {
+            builder = builder.external_addrs(parse_addrs(&external, "node.external_addrs")?);
+        }
+        if let Some(version) = node.protocol_version {
+            builder = builder.protocol_version(version);
+        }
+        if let Some(secs) = node.shutdown_timeout_secs {
+            builder = builder.shutdown_timeout(Duration::from_secs(secs));
+        }
+        if let Some(filter) = node.log {
+            builder = builder.log_filter(filter);
+        }
+
+        let gossipsub = self.gossipsub;
+        let mut settings = builder.current().gossipsub.clone();
+        if let Some(secs) = gossipsub.heartbeat_interval_secs {
+            settings.heartbeat_interval = Duration::from_secs(secs);
+        }
+        if let Some(mesh_n) = gossipsub.mesh_n {
+            settings.mesh_n = mesh_n;
+        }
+        if let Some(mesh_n_low) = gossipsub.mesh_n_low {
+            settings.mesh_n_low = mesh_n_low;
+        }
+        if let Some(mesh_n_high) = gossipsub.mesh_n_high {
+            settings.mesh_n_high = mesh_n_high;
+        }
+        if let Some(size) = gossipsub.max_transmit_size {
+            settings.max_transmit_size = size;
+        }
+        if let Some(mode) = gossipsub.message_id {
+            settings.message_id_mode = mode
+                .parse::<MessageIdMode>()
+                .map_err(|e| anyhow!("gossipsub.message_id: {}", e))?;
+        }
+        if let Some(topics) = gossipsub.content_addressed_topics {
+            settings.content_addressed_topics = topics;
+        }
+        builder = builder.gossipsub(settings);
+        if let Some(topics) = gossipsub.topics {
+            builder = builder.topics(topics);
+        }
+        let mut scoring = match gossipsub.peer_scoring {
+            Some(false) => None,
+            Some(true) => Some(builder.current().peer_scoring.clone().unwrap_or_default()),
+            None => builder.current().peer_scoring.clone(),
+        };
+        if let Some(scoring) = scoring.as_mut() {
+            if let Some(threshold) = gossipsub.gossip_threshold {
+                scoring.thresholds.gossip_threshold = threshold;
+            }
+            if let Some(threshold) = gossipsub.publish_threshold {
+                scoring.thresholds.publish_threshold = threshold;
+            }
+            if let Some(threshold) = gossipsub.graylist_threshold {
+                scoring.thresholds.graylist_threshold = threshold;
+            }
+        }
+        builder = builder.peer_scoring(scoring);
+
+        let dht = self.dht;
+        if let Some(enabled) = dht.enabled {
+            builder = builder.enable_dht(enabled);
+        }
+        if let Some(mode) = dht.mode {
+            let mode = mode.parse::<KademliaMode>().map_err(|e| anyhow!("dht.mode: {}", e))?;
+            builder = builder.kademlia_mode(mode);
+        }
+        if let Some(bootstrap) = dht.bootstrap {
+            builder = builder.use_bootstrap(bootstrap);
+        }
+        if dht.bootstrap_peers.is_some() || dht.bootstrap_file.is_some() {
+            let mut peers = parse_addrs(&dht.bootstrap_peers.unwrap_or_default(), "dht.bootstrap_peers")?;
+            if let Some(path) = dht.bootstrap_file {
+                peers.extend(load_bootstrap_file(&path)?);
+            }
+            builder = builder.bootstrap_peers(peers);
+        }
+        if let Some(secs) = dht.bootstrap_interval_secs {
+            builder = builder.bootstrap_interval(Duration::from_secs(secs));
+        }
+        let mut record_store = builder.current().record_store.clone();
+        if let Some(max_records) = dht.max_records {
+            record_store.max_records = max_records;
+        }
+        if let Some(size) = dht.max_record_size {
+            record_store.max_value_bytes = size;
+        }
+        if let Some(max_providers) = dht.max_providers_per_key {
+            record_store.max_providers_per_key = max_providers;
+        }
+        if let Some(secs) = dht.record_ttl_secs {
+            record_store.record_ttl = Duration::from_secs(secs);
+        }
+        if let Some(secs) = dht.republish_interval_secs {
+            record_store.republish_interval = Duration::from_secs(secs);
+        }
+        if let Some(secs) = dht.provider_ttl_secs {
+            record_store.provider_ttl = Duration::from_secs(secs);
+        }
+        if let Some(secs) = dht.reprovide_interval_secs {
+            record_store.reprovide_interval = Duration::from_secs(secs);
+        }
+        builder = builder.record_store(record_store);
+        if let Some(path) = dht.record_store {
+            builder = builder.dht_store(path);
+        }
+        if let Some(keys) = dht.provide {
+            builder = builder.provide(keys);
+        }
+        if let Some(dial) = dht.dial_providers {
+            builder = builder.dial_providers(dial);
+        }
+
+        if let Some(enabled) = self.mdns.enabled {
+            builder = builder.enable_mdns(enabled);
+        }
+
+        let relay = self.relay;
+        if let Some(server) = relay.server {
+            builder = builder.relay_server(server);
+        }
+        if let Some(via) = relay.via {
+            builder = builder.relay_via(parse_addrs(&via, "relay.via")?);
+        }
+
+        let handshake = self.handshake;
+        if let Some(topic) = handshake.topic {
+            builder = builder.handshake_topic(topic);
+        }
+        if let Some(secs) = handshake.interval_secs {
+            builder = builder.handshake_interval(Duration::from_secs(secs));
+        }
+        if let Some(secs) = handshake.max_clock_skew_secs {
+            builder = builder.handshake_max_clock_skew(Duration::from_secs(secs));
+        }
+
+        if let Some(addr) = self.admin.listen {
+            builder = builder.admin_api(Some(addr));
+        }
+        if let Some(addr) = self.metrics.listen {
+            builder = builder.metrics(Some(addr));
+        }
+
+        Ok(builder)
+    }
+
+    /// Render the effective values of `config`, e.g. for `node_eeb config check`.
+    pub fn from_config(config: &NodeConfig) -> Self {
+        let GossipsubSettings {
+            heartbeat_interval,
+            mesh_n,
+            mesh_n_low,
+            mesh_n_high,
+            max_transmit_size,
+            message_id_mode,
+            content_addressed_topics,
+        } = &config.gossipsub;
+
+        Self {
+            node: NodeSection {
+                name: config.node_name.clone(),
+                identity: config.identity_file.clone(),
+                swarm_key: config.swarm_key_file.clone(),
+                peer_store: config.peer_store_file.clone(),
+                connect: Some(config.connect.iter().map(|a| a.to_string()).collect()),
+                listen: Some(config.listen_addrs.iter().map(|a| a.to_string()).collect()),
+                websocket_port: config.websocket_port,
+                external_addrs: Some(config.external_addrs.iter().map(|a| a.to_string()).collect()),
+                protocol_version: Some(config.protocol_version.clone()),
+                shutdown_timeout_secs: Some(config.shutdown_timeout.as_secs()),
+                log: Some(config.log_filter.clone()),
+                interactive: None,
+            },
+            gossipsub: GossipsubSection {
+                heartbeat_interval_secs: Some(heartbeat_interval.as_secs()),
+                mesh_n: Some(*mesh_n),
+                mesh_n_low: Some(*mesh_n_low),
+                mesh_n_high: Some(*mesh_n_high),
+                max_transmit_size: Some(*max_transmit_size),
+                message_id: Some(message_id_mode.to_string()),
+                content_addressed_topics: Some(content_addressed_topics.clone()),
+                topics: Some(config.topics.clone()),
+                peer_scoring: Some(config.peer_scoring.is_some()),
+                gossip_threshold: config.peer_scoring.as_ref().map(|s| s.thresholds.gossip_threshold),
+                publish_threshold: config.peer_scoring.as_ref().map(|s| s.thresholds.publish_threshold),
+                graylist_threshold: config.peer_scoring.as_ref().map(|s| s.thresholds.graylist_threshold),
+            },
+            dht: DhtSection {
+                enabled: Some(config.enable_dht),
+                mode: Some(config.kademlia_mode.to_string()),
+                bootstrap: Some(config.use_bootstrap),
+                bootstrap_peers: Some(config.bootstrap_peers.iter().map(|a| a.to_string()).collect()),
+                bootstrap_file: None,
+                bootstrap_interval_secs: Some(config.bootstrap_interval.as_secs()),
+                record_store: config.record_store.path.clone(),
+                max_records: Some(config.record_store.max_records),
+                max_record_size: Some(config.record_store.max_value_bytes),
+                max_providers_per_key: Some(config.record_store.max_providers_per_key),
+                record_ttl_secs: Some(config.record_store.record_ttl.as_secs()),
+                republish_interval_secs: Some(config.record_store.republish_interval.as_secs()),
+                provider_ttl_secs: Some(config.record_store.provider_ttl.as_secs()),
+                reprovide_interval_secs: Some(config.record_store.reprovide_interval.as_secs()),
+                provide: Some(config.provided_keys.clone()),
+                dial_providers: Some(config.dial_providers),
+            },
+            mdns: MdnsSection {
+                enabled: Some(config.enable_mdns),
+            },
+            relay: RelaySection {
+                server: Some(config.relay_server),
+                via: Some(config.relay_via.iter().map(|a| a.to_string()).collect()),
+            },
+            handshake: HandshakeSection {
+                topic: Some(config.handshake_topic.clone()),
+                interval_secs: Some(config.handshake_interval.as_secs()),
+                max_clock_skew_secs: Some(config.handshake_max_clock_skew.as_secs()),
+            },
+            admin: AdminSection {
+                listen: config.admin_listen,
+            },
+            metrics: MetricsSection {
+                listen: config.metrics_listen,
+            },
+        }
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string_pretty(self).map_err(|e| anyhow!("Failed to serialize config: {}", e))
+    }
+}
+
+fn parse_addrs(addrs: &[String], field: &str) -> Result<Vec<Multiaddr>> {
+    addrs
+        .iter()
+        .map(|addr| {
+            addr.parse()
+                .map_err(|e| anyhow!("{}: invalid multiaddr '{}': {}", field, addr, e))
+        })
+        .collect()
+}
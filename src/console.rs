@@ -0,0 +1,170 @@
+//! Interactive console for `node_eeb --interactive`.
+
+use anyhow::{anyhow, Result};
+use libp2p::Multiaddr;
+use node_eeb::{EventStream, NodeEvent, NodeHandle, PutRecordOptions, RecordQuorum};
+use tokio::io::{stdin, AsyncBufReadExt, BufReader};
+
+use crate::dht_cli;
+
+/// Topic that plain lines are published to until `/sub` picks another one.
+const CHAT_TOPIC: &str = "chat";
+
+const HELP: &str = "\
+Commands:
+  /peers                   list connected peers
+  /dial <addr>             connect to a peer (addr ends in /p2p/<peer-id>)
+  /sub <topic>             subscribe and send plain lines to <topic>
+  /unsub <topic>           unsubscribe from <topic>
+  /topics                  list subscribed topics
+  /pub <topic> <message>   publish to <topic>
+  /dht get <key>           look up a DHT record
+  /dht put <key> <value>   store a DHT record
+  /dht provide <key>       announce that we provide <key>
+  /dht unprovide <key>     stop announcing <key>
+  /dht providers <key>     find the peers providing <key>
+  /whoami                  show our peer ID and addresses
+  /quit                    shut the node down
+Anything else is published to the current topic.";
+
+/// Read commands from stdin and print incoming messages until stdin closes or `/quit`.
+pub async fn run(node: NodeHandle, events: EventStream) {
+    tokio::spawn(print_events(events));
+
+    if let Err(e) = node.subscribe(CHAT_TOPIC).await {
+        println!("❌ Failed to join '{}': {}", CHAT_TOPIC, e);
+    }
+    println!("💬 Interactive mode, publishing to '{}'. Type /help for commands.", CHAT_TOPIC);
+    let mut topic = CHAT_TOPIC.to_string();
+    let mut lines = BufReader::new(stdin()).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line == "/quit" {
+            if let Err(e) = node.shutdown().await {
+                println!("❌ {}", e);
+            }
+            return;
+        }
+        if let Err(e) = execute(&node, &mut topic, line).await {
+            println!("❌ {}", e);
+        }
+    }
+}
+
+async fn execute(node: &NodeHandle, topic: &mut String, line: &str) -> Result<()> {
+    let Some(command) = line.strip_prefix('/') else {
+        node.publish(topic.clone(), line).await?;
+        return Ok(());
+    };
+
+    let mut parts = command.splitn(2, ' ');
+    let name = parts.next().unwrap_or_default();
+    let rest = parts.next().unwrap_or_default().trim();
+
+    match name {
+        "help" => println!("{}", HELP),
+        "peers" => {
+            let peers = node.connected_peers().await?;
+            println!("{} connected peers", peers.len());
+            for peer in peers {
+                let addrs: Vec<String> = peer.addresses.iter().map(|a| a.to_string()).collect();
+                println!("  {} {}", peer.peer_id, addrs.join(", "));
+            }
+        }
+        "dial" => {
+            let addr: Multiaddr = rest.parse().map_err(|e| anyhow!("invalid multiaddr '{}': {}", rest, e))?;
+            node.dial(addr).await?;
+        }
+        "sub" => {
+            let new_topic = required(rest, "/sub <topic>")?;
+            node.subscribe(new_topic).await?;
+            *topic = new_topic.to_string();
+            println!("🎯 Subscribed to '{}', plain lines now go there", topic);
+        }
+        "unsub" => {
+            let old_topic = required(rest, "/unsub <topic>")?;
+            if node.unsubscribe(old_topic).await? {
+                println!("Unsubscribed from '{}'", old_topic);
+            }
+        }
+        "topics" => {
+            for info in node.topics().await? {
+                println!("  {} ({} mesh peers)", info.topic, info.mesh_peers);
+            }
+        }
+        "pub" => {
+            let (target, message) = rest.split_once(' ').ok_or_else(|| anyhow!("usage: /pub <topic> <message>"))?;
+            node.publish(target, message.trim()).await?;
+        }
+        "dht" => {
+            let mut args = rest.splitn(3, ' ');
+            match (args.next(), args.next(), args.next()) {
+                (Some("get"), Some(key), None) => {
+                    let outcome = node.get_record(key, RecordQuorum::One).await?;
+                    dht_cli::print_get(key, &outcome);
+                }
+                (Some("put"), Some(key), Some(value)) => {
+                    let outcome = node.put_record(key, value, PutRecordOptions::default()).await?;
+                    dht_cli::print_put(key, &outcome);
+                }
+                (Some("provide"), Some(key), None) => {
+                    node.start_providing(key).await?;
+                    println!("📦 Providing {}", key);
+                }
+                (Some("unprovide"), Some(key), None) => {
+                    node.stop_providing(key).await?;
+                    println!("📦 No longer providing {}", key);
+                }
+                (Some("providers"), Some(key), None) => {
+                    let providers = node.get_providers(key).await?;
+                    dht_cli::print_providers(key, &providers);
+                }
+                _ => return Err(anyhow!("usage: /dht get|put|provide|unprovide|providers <key> [value]")),
+            }
+        }
+        "whoami" => {
+            let addrs = node.listen_addrs().await?;
+            println!("🆔 {}", node.local_peer_id());
+            for addr in addrs.listen.iter().chain(&addrs.external) {
+                println!("  {}", addr);
+            }
+        }
+        _ => return Err(anyhow!("unknown command /{}, try /help", name)),
+    }
+    Ok(())
+}
+
+fn required<'a>(arg: &'a str, usage: &str) -> Result<&'a str> {
+    if arg.is_empty() {
+        Err(anyhow!("usage: {}", usage))
+    } else {
+        Ok(arg)
+    }
+}
+
+async fn print_events(mut events: EventStream) {
+    while let Some(event) = events.recv().await {
+        match event {
+            NodeEvent::MessageReceived { topic, source, data, .. } => {
+                let from = source.map_or_else(|| "unknown".to_string(), |peer_id| short(&peer_id.to_string()));
+                println!("[{}] {}: {}", topic, from, String::from_utf8_lossy(&data));
+            }
+            NodeEvent::HandshakeReceived { handshake, .. } if handshake.leaving => {
+                println!("👋 {} left", handshake.node_name.unwrap_or_else(|| short(&handshake.peer_id)));
+            }
+            NodeEvent::PeerConnected { peer_id, .. } => println!("🤝 {} connected", short(&peer_id.to_string())),
+            NodeEvent::PeerDisconnected { peer_id } => println!("👋 {} disconnected", short(&peer_id.to_string())),
+            _ => {}
+        }
+    }
+}
+
+/// Last characters of a peer ID, enough to tell peers apart in chat.
+fn short(peer_id: &str) -> String {
+    let start = peer_id.len().saturating_sub(8);
+    format!("…{}", &peer_id[start..])
+}
@@ -0,0 +1,165 @@
+//! `node_eeb dht get|put|providers`: one DHT query from a short-lived node
+//! with its own throwaway identity, see `NodeConfigBuilder::ephemeral`.
+
+use anyhow::{bail, Result};
+use clap::Subcommand;
+use libp2p::PeerId;
+use node_eeb::{
+    FoundRecord, GetRecordOutcome, NodeConfig, NodeEvent, NodeHandle, P2PNode, PutRecordOptions, PutRecordOutcome,
+    RecordQuorum,
+};
+use std::time::Duration;
+use tokio::time::timeout;
+
+/// How long to wait for a first peer before querying with only the local store.
+const JOIN_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum DhtCommand {
+    /// Look up a record
+    Get {
+        key: String,
+        /// Peers that must return the record: one, majority, all or a number
+        #[arg(long, default_value = "one")]
+        quorum: RecordQuorum,
+    },
+    /// Store a record
+    Put {
+        key: String,
+        value: String,
+        /// Peers that must store the record: one, majority, all or a number
+        #[arg(long, default_value = "one")]
+        quorum: RecordQuorum,
+        /// Seconds until the record expires (defaults to the configured record TTL)
+        #[arg(long, value_name = "SECS")]
+        ttl: Option<u64>,
+        /// Peer to publish the record on behalf of (defaults to the query node's throwaway ID)
+        #[arg(long, value_name = "PEER_ID")]
+        publisher: Option<PeerId>,
+    },
+    /// Find the peers providing a key
+    Providers { key: String },
+}
+
+/// Join the network, run `command`, print its result and shut down again.
+pub async fn run(config: NodeConfig, command: &DhtCommand) -> Result<()> {
+    let mut node = P2PNode::new(config).await?;
+    let handle = node.handle();
+    let mut events = node.subscribe_events();
+    let running = tokio::spawn(async move { node.run().await });
+
+    // Identify fills the routing table, so queries have somewhere to go after it
+    let joined = timeout(JOIN_TIMEOUT, async {
+        while let Some(event) = events.recv().await {
+            if matches!(event, NodeEvent::PeerIdentified { .. }) {
+                return true;
+            }
+        }
+        false
+    })
+    .await
+    .unwrap_or(false);
+    if !joined {
+        eprintln!("⚠️ No peers found within {}s, querying anyway", JOIN_TIMEOUT.as_secs());
+    }
+
+    let result = query(&handle, command).await;
+    handle.shutdown().await?;
+    running.await??;
+    result
+}
+
+async fn query(node: &NodeHandle, command: &DhtCommand) -> Result<()> {
+    match command {
+        DhtCommand::Get { key, quorum } => {
+            let outcome = node.get_record(key.as_str(), *quorum).await?;
+            print_get(key, &outcome);
+            match outcome {
+                GetRecordOutcome::Found { .. } => Ok(()),
+                GetRecordOutcome::NotFound => bail!("record not found"),
+                GetRecordOutcome::QuorumFailed { .. } => bail!("quorum not reached"),
+            }
+        }
+        DhtCommand::Put { key, value, quorum, ttl, publisher } => {
+            let options = PutRecordOptions {
+                quorum: *quorum,
+                ttl: ttl.map(Duration::from_secs),
+                publisher: *publisher,
+            };
+            let outcome = node.put_record(key.as_str(), value.as_str(), options).await?;
+            print_put(key, &outcome);
+            match outcome {
+                PutRecordOutcome::Stored => Ok(()),
+                PutRecordOutcome::QuorumFailed { .. } => bail!("quorum not reached"),
+            }
+        }
+        DhtCommand::Providers { key } => {
+            let providers = node.get_providers(key.as_str()).await?;
+            print_providers(key, &providers);
+            if providers.is_empty() {
+                bail!("no providers found");
+            }
+            Ok(())
+        }
+    }
+}
+
+pub fn print_get(key: &str, outcome: &GetRecordOutcome) {
+    match outcome {
+        GetRecordOutcome::Found { records } => {
+            for record in records {
+                print_record(key, record);
+            }
+        }
+        GetRecordOutcome::NotFound => println!("🔑 {} not found", key),
+        GetRecordOutcome::QuorumFailed { records, quorum } => {
+            println!("⚠️ Only {} of {} peers returned {}", records.len(), quorum, key);
+            for record in records {
+                print_record(key, record);
+            }
+        }
+    }
+}
+
+pub fn print_put(key: &str, outcome: &PutRecordOutcome) {
+    match outcome {
+        PutRecordOutcome::Stored => println!("🔑 Stored {}", key),
+        PutRecordOutcome::QuorumFailed { stored_at, quorum } => println!(
+            "⚠️ Stored {} locally and at {} peers, {} needed",
+            key,
+            stored_at.len(),
+            quorum
+        ),
+    }
+}
+
+pub fn print_providers(key: &str, providers: &[PeerId]) {
+    if providers.is_empty() {
+        println!("📦 No providers of {}", key);
+        return;
+    }
+    println!("📦 {} providers of {}", providers.len(), key);
+    for peer_id in providers {
+        println!("  {}", peer_id);
+    }
+}
+
+fn print_record(key: &str, record: &FoundRecord) {
+    let publisher = record
+        .publisher
+        .map_or_else(|| "unknown".to_string(), |peer_id| peer_id.to_string());
+    let source = record
+        .peer
+        .map_or_else(|| "local store".to_string(), |peer_id| peer_id.to_string());
+    let expiry = record
+        .expires_in_secs
+        .map_or_else(String::new, |secs| format!(", expires in {}s", secs));
+    println!(
+        "🔑 {} = {} (published by {}, from {}{})",
+        key,
+        String::from_utf8_lossy(&record.value),
+        publisher,
+        source,
+        expiry
+    );
+}
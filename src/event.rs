@@ -0,0 +1,84 @@
+use libp2p::{autonat::NatStatus, Multiaddr, PeerId};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+
+use crate::{handshake::HandshakeInfo, p2p_node::HandshakeMessage};
+
+/// Events buffered per subscriber before the oldest are dropped.
+pub(crate) const EVENT_BUFFER: usize = 256;
+
+/// Something that happened on the node, delivered through [`EventStream`].
+#[derive(Debug, Clone)]
+pub enum NodeEvent {
+    /// First connection to a peer was established.
+    PeerConnected { peer_id: PeerId, address: Multiaddr },
+    /// Last connection to a peer was closed.
+    PeerDisconnected { peer_id: PeerId },
+    /// A peer answered the identify protocol.
+    PeerIdentified {
+        peer_id: PeerId,
+        protocol_version: String,
+        agent_version: String,
+        listen_addrs: Vec<Multiaddr>,
+        /// Protocols the peer supports, e.g. `/ipfs/kad/1.0.0`.
+        protocols: Vec<String>,
+    },
+    /// A peer completed the handshake protocol with a compatible version.
+    HandshakeCompleted { peer_id: PeerId, info: HandshakeInfo },
+    /// A valid handshake arrived on the handshake topic. `peer_id` is the
+    /// peer that published it, not the one that forwarded it to us.
+    HandshakeReceived { peer_id: PeerId, handshake: HandshakeMessage },
+    /// A gossipsub message arrived on any other subscribed topic.
+    MessageReceived {
+        topic: String,
+        /// Original publisher, as opposed to the peer that forwarded it to us.
+        source: Option<PeerId>,
+        propagation_source: PeerId,
+        sequence_number: Option<u64>,
+        message_id: String,
+        data: Vec<u8>,
+    },
+    /// Our provider record for `key` reached the peers closest to it, either
+    /// when first announced or when announced again.
+    ProviderAnnounced { key: Vec<u8> },
+    /// A provider lookup found peers providing `key` that it had not
+    /// reported yet; one lookup can report several batches.
+    ProvidersFound { key: Vec<u8>, providers: Vec<PeerId> },
+    /// AutoNAT changed its verdict on our reachability.
+    NatStatusChanged { old: NatStatus, new: NatStatus },
+    /// The node started listening on a new address.
+    ListenAddrAdded { address: Multiaddr },
+    /// A relay accepted (or renewed) our reservation.
+    RelayReservation { relay_peer_id: PeerId, renewal: bool },
+    /// Outcome of a DCUtR hole punch attempt with a peer.
+    HolePunchResult { peer_id: PeerId, result: Result<(), String> },
+    /// The subscriber fell behind and `missed` events were dropped.
+    Lagged { missed: u64 },
+}
+
+/// Subscription to [`NodeEvent`]s, created with
+/// [`P2PNode::subscribe_events`](crate::P2PNode::subscribe_events).
+///
+/// Each subscriber has its own bounded buffer; a slow subscriber never blocks
+/// the node but receives [`NodeEvent::Lagged`] for the events it missed.
+pub struct EventStream {
+    receiver: broadcast::Receiver<NodeEvent>,
+}
+
+impl EventStream {
+    pub(crate) fn new(receiver: broadcast::Receiver<NodeEvent>) -> Self {
+        Self { receiver }
+    }
+
+    /// Wait for the next event. Returns `None` once the node has shut down.
+    pub async fn recv(&mut self) -> Option<NodeEvent> {
+        match self.receiver.recv().await {
+            Ok(event) => Some(event),
+            Err(RecvError::Lagged(missed)) => {
+                warn!("Event subscriber lagged behind, {} events dropped", missed);
+                Some(NodeEvent::Lagged { missed })
+            }
+            Err(RecvError::Closed) => None,
+        }
+    }
+}
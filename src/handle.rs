@@ -0,0 +1,154 @@
+use anyhow::{anyhow, Result};
+use libp2p::{Multiaddr, PeerId};
+use serde::Serialize;
+use std::fmt;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::command::{
+    Command, GetRecordOutcome, ListenAddrs, PeerInfo, PutRecordOptions, PutRecordOutcome, RecordQuorum, TopicInfo,
+};
+
+/// Returned by [`NodeHandle`] methods once the node's swarm loop has stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeStopped;
+
+impl fmt::Display for NodeStopped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("node is not running")
+    }
+}
+
+impl std::error::Error for NodeStopped {}
+
+/// Cloneable handle for driving a [`P2PNode`](crate::P2PNode) while
+/// [`run`](crate::P2PNode::run) owns it, obtained with [`P2PNode::handle`](crate::P2PNode::handle).
+///
+/// Each call is sent into the swarm loop and answered from there, so it only
+/// completes while the node is running; calls made earlier wait for
+/// [`run`](crate::P2PNode::run) to start. Once `run` returns, calls fail with
+/// [`NodeStopped`].
+#[derive(Debug, Clone)]
+pub struct NodeHandle {
+    commands: mpsc::Sender<Command>,
+    local_peer_id: PeerId,
+}
+
+impl NodeHandle {
+    pub(crate) fn new(commands: mpsc::Sender<Command>, local_peer_id: PeerId) -> Self {
+        Self { commands, local_peer_id }
+    }
+
+    pub fn local_peer_id(&self) -> PeerId {
+        self.local_peer_id
+    }
+
+    /// Dial a peer; `addr` must end in `/p2p/<peer-id>`.
+    pub async fn dial(&self, addr: Multiaddr) -> Result<()> {
+        self.request(|reply| Command::Dial { addr, reply }).await?
+    }
+
+    /// Publish `data` on a gossipsub topic and return the message ID.
+    pub async fn publish(&self, topic: impl Into<String>, data: impl Into<Vec<u8>>) -> Result<String> {
+        let (topic, data) = (topic.into(), data.into());
+        self.request(|reply| Command::Publish { topic, data, reply }).await?
+    }
+
+    /// Publish `value` serialized as JSON on a gossipsub topic and return the message ID.
+    pub async fn publish_json<T: Serialize>(&self, topic: impl Into<String>, value: &T) -> Result<String> {
+        self.publish(topic, serde_json::to_vec(value)?).await
+    }
+
+    /// Subscribe to a gossipsub topic. Messages on it arrive as
+    /// [`NodeEvent::MessageReceived`](crate::NodeEvent::MessageReceived).
+    /// Returns `false` if already subscribed.
+    pub async fn subscribe(&self, topic: impl Into<String>) -> Result<bool> {
+        let topic = topic.into();
+        self.request(|reply| Command::Subscribe { topic, reply }).await?
+    }
+
+    /// Unsubscribe from a gossipsub topic. Returns `false` if not subscribed.
+    pub async fn unsubscribe(&self, topic: impl Into<String>) -> Result<bool> {
+        let topic = topic.into();
+        self.request(|reply| Command::Unsubscribe { topic, reply }).await?
+    }
+
+    pub async fn connected_peers(&self) -> Result<Vec<PeerInfo>> {
+        self.request(|reply| Command::ConnectedPeers { reply }).await
+    }
+
+    pub async fn listen_addrs(&self) -> Result<ListenAddrs> {
+        self.request(|reply| Command::ListenAddrs { reply }).await
+    }
+
+    pub async fn topics(&self) -> Result<Vec<TopicInfo>> {
+        self.request(|reply| Command::Topics { reply }).await
+    }
+
+    /// Peers in the Kademlia routing table; empty when the DHT is disabled.
+    pub async fn routing_table(&self) -> Result<Vec<PeerInfo>> {
+        self.request(|reply| Command::RoutingTable { reply }).await
+    }
+
+    /// Close every connection to `peer_id`.
+    pub async fn disconnect(&self, peer_id: PeerId) -> Result<()> {
+        self.request(|reply| Command::Disconnect { peer_id, reply }).await?
+    }
+
+    /// Ask the DHT for the peers closest to `key`.
+    pub async fn get_closest_peers(&self, key: PeerId) -> Result<Vec<PeerId>> {
+        self.request(|reply| Command::GetClosestPeers { key, reply }).await?
+    }
+
+    /// Store a record in our own store and at the peers closest to `key`,
+    /// waiting until the quorum is reached or the query gives up.
+    pub async fn put_record(
+        &self,
+        key: impl Into<Vec<u8>>,
+        value: impl Into<Vec<u8>>,
+        options: PutRecordOptions,
+    ) -> Result<PutRecordOutcome> {
+        let (key, value) = (key.into(), value.into());
+        self.request(|reply| Command::PutRecord { key, value, options, reply }).await?
+    }
+
+    /// Look up a record in the DHT, waiting until `quorum` peers (counting
+    /// our own store) returned it or the query gives up.
+    pub async fn get_record(&self, key: impl Into<Vec<u8>>, quorum: RecordQuorum) -> Result<GetRecordOutcome> {
+        let key = key.into();
+        self.request(|reply| Command::GetRecord { key, quorum, reply }).await?
+    }
+
+    /// Announce that we provide `key`: keep a provider record in our own store,
+    /// send it to the peers closest to `key` and announce it again every
+    /// reprovide interval until [`stop_providing`](Self::stop_providing).
+    pub async fn start_providing(&self, key: impl Into<Vec<u8>>) -> Result<()> {
+        let key = key.into();
+        self.request(|reply| Command::StartProviding { key, reply }).await?
+    }
+
+    /// Stop announcing `key`. Records already sent to other peers stay until they expire.
+    pub async fn stop_providing(&self, key: impl Into<Vec<u8>>) -> Result<()> {
+        let key = key.into();
+        self.request(|reply| Command::StopProviding { key, reply }).await?
+    }
+
+    /// Look up the peers providing `key`, including ourselves. Providers we
+    /// are not connected to are dialed unless disabled with
+    /// [`NodeConfigBuilder::dial_providers`](crate::NodeConfigBuilder::dial_providers).
+    pub async fn get_providers(&self, key: impl Into<Vec<u8>>) -> Result<Vec<PeerId>> {
+        let key = key.into();
+        self.request(|reply| Command::GetProviders { key, reply }).await?
+    }
+
+    /// Stop the node; [`run`](crate::P2PNode::run) returns once this completes.
+    pub async fn shutdown(&self) -> Result<()> {
+        self.request(|reply| Command::Shutdown { reply }).await
+    }
+
+    /// Send a command built around a fresh reply channel and wait for the answer.
+    async fn request<T>(&self, command: impl FnOnce(oneshot::Sender<T>) -> Command) -> Result<T> {
+        let (reply, response) = oneshot::channel();
+        self.commands.send(command(reply)).await.map_err(|_| anyhow!(NodeStopped))?;
+        response.await.map_err(|_| anyhow!(NodeStopped))
+    }
+}
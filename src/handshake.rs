@@ -0,0 +1,153 @@
+//! Signed handshake exchanged over `/node-eeb/handshake/1.0.0` when a
+//! connection is established.
+//!
+//! The dialing side sends its [`HandshakeInfo`] as a request and the listener
+//! answers with its own, so both ends learn about each other in one round trip.
+
+use anyhow::{anyhow, Result};
+use libp2p::{
+    identity::{Keypair, PublicKey},
+    request_response::{self, ProtocolSupport},
+    PeerId, StreamProtocol,
+};
+use serde::{Deserialize, Serialize};
+
+pub const HANDSHAKE_PROTOCOL: StreamProtocol = StreamProtocol::new("/node-eeb/handshake/1.0.0");
+
+/// Software version sent in every handshake.
+pub(crate) const AGENT_VERSION: &str = concat!("node_eeb/", env!("CARGO_PKG_VERSION"));
+
+pub(crate) type Behaviour = request_response::json::Behaviour<SignedHandshake, SignedHandshake>;
+
+pub(crate) fn behaviour() -> Behaviour {
+    request_response::json::Behaviour::new(
+        [(HANDSHAKE_PROTOCOL, ProtocolSupport::Full)],
+        request_response::Config::default(),
+    )
+}
+
+/// What a node tells a newly connected peer about itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HandshakeInfo {
+    /// Application protocol version, e.g. `/node-eeb/1.0.0`.
+    pub protocol_version: String,
+    pub node_name: Option<String>,
+    /// Software name and version, e.g. `node_eeb/0.1.0`.
+    pub agent_version: String,
+    /// Optional features the node offers, such as `dht` or `relay`.
+    pub capabilities: Vec<String>,
+    /// Gossipsub topics the node was subscribed to when it sent the handshake.
+    pub topics: Vec<String>,
+    /// Unix time in seconds.
+    pub timestamp: u64,
+}
+
+impl HandshakeInfo {
+    /// Capabilities both we and the peer offer.
+    pub fn shared_capabilities(&self, ours: &[String]) -> Vec<String> {
+        self.capabilities
+            .iter()
+            .filter(|capability| ours.contains(capability))
+            .cloned()
+            .collect()
+    }
+}
+
+/// [`HandshakeInfo`] as sent on the wire, signed with the sender's identity key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedHandshake {
+    payload: Vec<u8>,
+    public_key: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl SignedHandshake {
+    pub(crate) fn sign(info: &HandshakeInfo, key: &Keypair) -> Result<Self> {
+        let payload = serde_json::to_vec(info)?;
+        let signature = key
+            .sign(&signing_input(&payload))
+            .map_err(|e| anyhow!("Failed to sign handshake: {}", e))?;
+        Ok(Self {
+            payload,
+            public_key: key.public().encode_protobuf(),
+            signature,
+        })
+    }
+
+    /// Decode the handshake, checking that `peer_id` is the one who signed it.
+    pub(crate) fn verify(&self, peer_id: &PeerId) -> Result<HandshakeInfo> {
+        let public_key = PublicKey::try_decode_protobuf(&self.public_key)
+            .map_err(|e| anyhow!("invalid public key: {}", e))?;
+        if public_key.to_peer_id() != *peer_id {
+            return Err(anyhow!("signed by {} instead", public_key.to_peer_id()));
+        }
+        if !public_key.verify(&signing_input(&self.payload), &self.signature) {
+            return Err(anyhow!("signature does not match"));
+        }
+        serde_json::from_slice(&self.payload).map_err(|e| anyhow!("malformed payload: {}", e))
+    }
+}
+
+/// Prefix the payload with the protocol name so a handshake signature can
+/// never be replayed as a signature over anything else.
+fn signing_input(payload: &[u8]) -> Vec<u8> {
+    [HANDSHAKE_PROTOCOL.as_ref().as_bytes(), payload].concat()
+}
+
+/// Whether peers speaking `ours` and `theirs` understand each other: the
+/// protocol name and major version must match, e.g. `/node-eeb/1.0.0` and
+/// `/node-eeb/1.2.0`.
+pub(crate) fn compatible(ours: &str, theirs: &str) -> bool {
+    fn name_and_major(version: &str) -> (&str, &str) {
+        let (name, number) = version.rsplit_once('/').unwrap_or(("", version));
+        (name, number.split('.').next().unwrap_or(number))
+    }
+    name_and_major(ours) == name_and_major(theirs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info() -> HandshakeInfo {
+        HandshakeInfo {
+            protocol_version: "/node-eeb/1.0.0".to_string(),
+            node_name: Some("test".to_string()),
+            agent_version: AGENT_VERSION.to_string(),
+            capabilities: vec!["gossipsub".to_string(), "dht".to_string()],
+            topics: vec!["chat".to_string()],
+            timestamp: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn verify_accepts_the_signer() {
+        let key = Keypair::generate_ed25519();
+        let signed = SignedHandshake::sign(&info(), &key).unwrap();
+        assert_eq!(signed.verify(&key.public().to_peer_id()).unwrap(), info());
+    }
+
+    #[test]
+    fn verify_rejects_another_signer() {
+        let key = Keypair::generate_ed25519();
+        let signed = SignedHandshake::sign(&info(), &key).unwrap();
+        assert!(signed.verify(&PeerId::random()).is_err());
+    }
+
+    #[test]
+    fn verify_rejects_a_tampered_payload() {
+        let key = Keypair::generate_ed25519();
+        let mut signed = SignedHandshake::sign(&info(), &key).unwrap();
+        let mut tampered = info();
+        tampered.capabilities.push("relay".to_string());
+        signed.payload = serde_json::to_vec(&tampered).unwrap();
+        assert!(signed.verify(&key.public().to_peer_id()).is_err());
+    }
+
+    #[test]
+    fn compatible_requires_same_name_and_major_version() {
+        assert!(compatible("/node-eeb/1.0.0", "/node-eeb/1.2.3"));
+        assert!(!compatible("/node-eeb/1.0.0", "/node-eeb/2.0.0"));
+        assert!(!compatible("/node-eeb/1.0.0", "/other/1.0.0"));
+    }
+}
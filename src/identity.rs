@@ -0,0 +1,59 @@
+use anyhow::{anyhow, Context, Result};
+use libp2p::identity::Keypair;
+use std::{fs, io::Write, path::Path};
+use tracing::info;
+
+/// Load an ed25519 keypair from `path`, or generate one and write it there.
+///
+/// The keyfile holds the protobuf encoding of the keypair, the same format
+/// used by other libp2p implementations. New keyfiles are created with
+/// `0600` permissions since they contain the node's private key.
+pub fn load_or_generate(path: &Path) -> Result<Keypair> {
+    if path.exists() {
+        let keypair = load(path)?;
+        info!("🔑 Loaded identity from {}", path.display());
+        return Ok(keypair);
+    }
+
+    let keypair = Keypair::generate_ed25519();
+    let bytes = keypair
+        .to_protobuf_encoding()
+        .map_err(|e| anyhow!("Failed to encode identity: {}", e))?;
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+    }
+
+    let mut file = create_private_file(path)
+        .with_context(|| format!("Failed to create identity file {}", path.display()))?;
+    file.write_all(&bytes)
+        .with_context(|| format!("Failed to write identity file {}", path.display()))?;
+
+    info!("🔑 Generated new identity at {}", path.display());
+    Ok(keypair)
+}
+
+/// Load the keypair stored at `path`, failing if the file does not exist.
+pub fn load(path: &Path) -> Result<Keypair> {
+    let bytes = fs::read(path)
+        .with_context(|| format!("Failed to read identity file {}", path.display()))?;
+    Keypair::from_protobuf_encoding(&bytes)
+        .map_err(|e| anyhow!("Invalid identity file {}: {}", path.display(), e))
+}
+
+#[cfg(unix)]
+fn create_private_file(path: &Path) -> std::io::Result<fs::File> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+}
+
+#[cfg(not(unix))]
+fn create_private_file(path: &Path) -> std::io::Result<fs::File> {
+    fs::OpenOptions::new().write(true).create_new(true).open(path)
+}
@@ -0,0 +1,36 @@
+//! P2P node built on libp2p, usable as a library or through the `node_eeb` binary.
+//!
+//! The node combines gossipsub, Kademlia, mDNS, identify, ping, relay, DCUtR
+//! and AutoNAT. Embedders build a [`NodeConfig`], construct a [`P2PNode`] from
+//! it and drive it with [`P2PNode::run`] on their own tokio runtime. Other tasks
+//! control the running node through a [`NodeHandle`] and react to what happens
+//! on the network through [`P2PNode::subscribe_events`].
+
+mod admin;
+pub mod bootstrap;
+mod command;
+pub mod config;
+pub mod config_file;
+mod event;
+mod handle;
+mod handshake;
+pub mod identity;
+mod metrics;
+mod p2p_node;
+mod peer_store;
+mod record_store;
+mod transport;
+mod util;
+pub mod validation;
+
+pub use command::{
+    FoundRecord, GetRecordOutcome, ListenAddrs, PeerInfo, PutRecordOptions, PutRecordOutcome, RecordQuorum, TopicInfo,
+};
+pub use config::{ConfigError, GossipsubSettings, KademliaMode, MessageIdMode, NodeConfig, NodeConfigBuilder, PeerScoring,
+    RecordStoreSettings};
+pub use config_file::ConfigFile;
+pub use event::{EventStream, NodeEvent};
+pub use handle::{NodeHandle, NodeStopped};
+pub use handshake::{HandshakeInfo, HANDSHAKE_PROTOCOL};
+pub use p2p_node::{HandshakeMessage, P2PBehaviour, P2PNode};
+pub use validation::MessageValidator;
@@ -1,62 +1,366 @@
+mod console;
+mod dht_cli;
+
 use anyhow::Result;
-use clap::Parser;
-use tracing::info;
+use clap::{Parser, Subcommand};
+use libp2p::{Multiaddr, PeerId};
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
+use tracing::{error, info};
 use tracing_subscriber::{EnvFilter, fmt, prelude::*};
 
-mod p2p_node;
+use node_eeb::{bootstrap::load_bootstrap_file, config::{default_listen_addrs, DEFAULT_ADMIN_ADDR, DEFAULT_METRICS_ADDR}, identity, ConfigFile, NodeConfig, NodeConfigBuilder, NodeEvent, P2PNode};
 
-use p2p_node::P2PNode;
+use dht_cli::DhtCommand;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "P2P network node")]
 struct Args {
-  
-    #[arg(short, long)]
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// TOML configuration file; CLI flags and NODE_EEB_* variables override it
+    #[arg(long, env = "NODE_EEB_CONFIG", global = true)]
+    config: Option<PathBuf>,
+
+    /// Port for the default TCP and QUIC listeners on IPv4 and IPv6
+    #[arg(short, long, env = "NODE_EEB_PORT")]
     port: Option<u16>,
-    
-    #[arg(short, long)]
-    connect: Option<String>,
-    
-    #[arg(long, default_value = "true")]
-    bootstrap: bool,
-    
-    #[arg(long, default_value = "false")]
-    relay: bool,
-    
-    #[arg(short, long)]
+
+    /// Addresses to listen on, replacing the --port listeners (repeatable)
+    #[arg(long, value_delimiter = ',', env = "NODE_EEB_LISTEN")]
+    listen: Vec<Multiaddr>,
+
+    /// Also accept WebSocket connections on this port (/tcp/<port>/ws)
+    #[arg(long, env = "NODE_EEB_WS_PORT")]
+    ws_port: Option<u16>,
+
+    /// Publicly reachable address to announce (repeatable)
+    #[arg(long, value_delimiter = ',', env = "NODE_EEB_EXTERNAL_ADDR")]
+    external_addr: Vec<Multiaddr>,
+
+    /// Peer multiaddr ending in /p2p/<peer-id> to dial on startup (repeatable)
+    #[arg(short, long, value_delimiter = ',', env = "NODE_EEB_CONNECT")]
+    connect: Vec<Multiaddr>,
+
+    /// Dial the bootstrap peers on startup (--no-bootstrap to disable)
+    #[arg(long, env = "NODE_EEB_BOOTSTRAP", num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    bootstrap: Option<bool>,
+
+    #[arg(long, hide = true)]
+    no_bootstrap: bool,
+
+    /// Act as a relay server for other peers (--no-relay to disable)
+    #[arg(long, env = "NODE_EEB_RELAY", num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    relay: Option<bool>,
+
+    #[arg(long, hide = true)]
+    no_relay: bool,
+
+    #[arg(short, long, env = "NODE_EEB_NAME")]
     name: Option<String>,
-    
-    #[arg(long, default_value = "true")]
-    dht: bool,
-    
-    #[arg(long, default_value = "true")]
-    mdns: bool,
+
+    /// Enable the Kademlia DHT (--no-dht to disable)
+    #[arg(long, env = "NODE_EEB_DHT", num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    dht: Option<bool>,
+
+    #[arg(long, hide = true)]
+    no_dht: bool,
+
+    /// Enable mDNS local discovery (--no-mdns to disable)
+    #[arg(long, env = "NODE_EEB_MDNS", num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    mdns: Option<bool>,
+
+    #[arg(long, hide = true)]
+    no_mdns: bool,
+
+    /// Bootstrap peer multiaddr ending in /p2p/<peer-id>, replacing the public
+    /// IPFS list (repeatable)
+    #[arg(long, value_delimiter = ',', env = "NODE_EEB_BOOTSTRAP_PEERS")]
+    bootstrap_peer: Vec<Multiaddr>,
+
+    /// File of bootstrap peer multiaddrs, one per line
+    #[arg(long, env = "NODE_EEB_BOOTSTRAP_FILE")]
+    bootstrap_file: Option<PathBuf>,
+
+    /// Relay to reserve a slot on, ending in /p2p/<relay-id> (repeatable)
+    #[arg(long, value_delimiter = ',', env = "NODE_EEB_RELAY_VIA")]
+    relay_via: Vec<Multiaddr>,
+
+    /// Read chat messages and /commands from stdin; logs go to stderr
+    #[arg(long)]
+    interactive: bool,
+
+    /// Gossipsub topic to subscribe to and print messages from (repeatable)
+    #[arg(long, value_delimiter = ',', env = "NODE_EEB_TOPICS")]
+    topic: Vec<String>,
+
+    /// DHT key to announce ourselves as a provider of (repeatable)
+    #[arg(long, value_delimiter = ',', env = "NODE_EEB_PROVIDE")]
+    provide: Vec<String>,
+
+    /// Keyfile holding the node's ed25519 identity (generated if missing)
+    #[arg(long, env = "NODE_EEB_IDENTITY")]
+    identity: Option<PathBuf>,
+
+    /// JSON file remembering known peers, redialed on startup
+    #[arg(long, env = "NODE_EEB_PEER_STORE")]
+    peer_store: Option<PathBuf>,
+
+    /// JSON file the DHT records we store are saved to
+    #[arg(long, env = "NODE_EEB_DHT_STORE")]
+    dht_store: Option<PathBuf>,
+
+    /// Pre-shared key file (/key/swarm/psk/1.0.0/) for a private network
+    #[arg(long, env = "NODE_EEB_SWARM_KEY")]
+    swarm_key: Option<PathBuf>,
+
+    /// Seconds to wait for peers to be told we are leaving before exiting
+    #[arg(long, value_name = "SECS", env = "NODE_EEB_SHUTDOWN_TIMEOUT")]
+    shutdown_timeout: Option<u64>,
+
+    /// Tracing filter directive, e.g. "info,libp2p=debug"
+    #[arg(long, env = "NODE_EEB_LOG")]
+    log: Option<String>,
+
+    /// Serve the HTTP admin API (default address 127.0.0.1:4080)
+    #[arg(long, value_name = "ADDR", num_args = 0..=1, require_equals = true,
+          default_missing_value = DEFAULT_ADMIN_ADDR, env = "NODE_EEB_ADMIN")]
+    admin: Option<SocketAddr>,
+
+    /// Serve Prometheus metrics at /metrics (default address 127.0.0.1:9090)
+    #[arg(long, value_name = "ADDR", num_args = 0..=1, require_equals = true,
+          default_missing_value = DEFAULT_METRICS_ADDR, env = "NODE_EEB_METRICS")]
+    metrics: Option<SocketAddr>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the peer ID for a keyfile without starting the node
+    Identity {
+        /// Keyfile to read
+        path: PathBuf,
+        /// Create the keyfile with a new identity if it does not exist
+        #[arg(long)]
+        generate: bool,
+    },
+    /// Store or look up a DHT record or find providers, then exit
+    Dht {
+        #[command(subcommand)]
+        action: DhtCommand,
+    },
+    /// Inspect the node configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Validate and print the effective configuration after merging all sources
+    Check,
+}
+
+impl Args {
+    fn config_file(&self) -> Result<Option<ConfigFile>> {
+        self.config.as_deref().map(ConfigFile::load).transpose()
+    }
+
+    /// The node configuration: CLI > env > config file > defaults.
+    fn node_builder(&self, file: Option<ConfigFile>) -> Result<NodeConfigBuilder> {
+        let mut builder = NodeConfig::builder();
+        if let Some(file) = file {
+            builder = file.apply(builder)?;
+        }
+        self.apply(builder)
+    }
+
+    /// Whether to run the console, which only the binary knows about.
+    fn interactive(&self, file: Option<&ConfigFile>) -> bool {
+        self.interactive || file.and_then(|file| file.node.interactive).unwrap_or(false)
+    }
+
+    fn apply(&self, mut builder: NodeConfigBuilder) -> Result<NodeConfigBuilder> {
+        if let Some(name) = &self.name {
+            builder = builder.node_name(name.clone());
+        }
+        if let Some(port) = self.port {
+            builder = builder.listen_addrs(default_listen_addrs(port));
+        }
+        if !self.listen.is_empty() {
+            builder = builder.listen_addrs(self.listen.clone());
+        }
+        if let Some(port) = self.ws_port {
+            builder = builder.websocket_port(Some(port));
+        }
+        if !self.external_addr.is_empty() {
+            builder = builder.external_addrs(self.external_addr.clone());
+        }
+        if let Some(dht) = toggle(self.dht, self.no_dht) {
+            builder = builder.enable_dht(dht);
+        }
+        if let Some(mdns) = toggle(self.mdns, self.no_mdns) {
+            builder = builder.enable_mdns(mdns);
+        }
+        if let Some(bootstrap) = toggle(self.bootstrap, self.no_bootstrap) {
+            builder = builder.use_bootstrap(bootstrap);
+        }
+        if !self.bootstrap_peer.is_empty() || self.bootstrap_file.is_some() {
+            let mut peers = self.bootstrap_peer.clone();
+            if let Some(path) = &self.bootstrap_file {
+                peers.extend(load_bootstrap_file(path)?);
+            }
+            builder = builder.bootstrap_peers(peers);
+        }
+        if !self.connect.is_empty() {
+            builder = builder.connect(self.connect.clone());
+        }
+        if let Some(relay) = toggle(self.relay, self.no_relay) {
+            builder = builder.relay_server(relay);
+        }
+        if !self.relay_via.is_empty() {
+            builder = builder.relay_via(self.relay_via.clone());
+        }
+        if !self.topic.is_empty() {
+            builder = builder.topics(self.topic.clone());
+        }
+        if !self.provide.is_empty() {
+            builder = builder.provide(self.provide.clone());
+        }
+        if let Some(path) = &self.identity {
+            builder = builder.identity_file(path.clone());
+        }
+        if let Some(path) = &self.swarm_key {
+            builder = builder.swarm_key_file(path.clone());
+        }
+        if let Some(path) = &self.peer_store {
+            builder = builder.peer_store(path.clone());
+        }
+        if let Some(path) = &self.dht_store {
+            builder = builder.dht_store(path.clone());
+        }
+        if let Some(secs) = self.shutdown_timeout {
+            builder = builder.shutdown_timeout(Duration::from_secs(secs));
+        }
+        if let Some(filter) = &self.log {
+            builder = builder.log_filter(filter.clone());
+        }
+        if let Some(addr) = self.admin {
+            builder = builder.admin_api(Some(addr));
+        }
+        if let Some(addr) = self.metrics {
+            builder = builder.metrics(Some(addr));
+        }
+        Ok(builder)
+    }
+}
+
+/// Resolve a `--flag[=bool]` / `--no-flag` pair; `None` means neither was given.
+fn toggle(value: Option<bool>, negated: bool) -> Option<bool> {
+    if negated {
+        Some(false)
+    } else {
+        value
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let args = Args::parse();
 
+    // Subcommands run before logging is set up so their output stays clean
+    match &args.command {
+        Some(Command::Identity { path, generate }) => {
+            let keypair = if *generate && !path.exists() {
+                let keypair = identity::load_or_generate(path)?;
+                eprintln!("🔑 Generated new identity at {}", path.display());
+                keypair
+            } else {
+                identity::load(path)?
+            };
+            println!("{}", PeerId::from(keypair.public()));
+            return Ok(());
+        }
+        Some(Command::Config { action: ConfigCommand::Check }) => {
+            let file = args.config_file()?;
+            let interactive = args.interactive(file.as_ref());
+            let mut effective = ConfigFile::from_config(&args.node_builder(file)?.build()?);
+            effective.node.interactive = Some(interactive);
+            print!("{}", effective.to_toml()?);
+            return Ok(());
+        }
+        Some(Command::Dht { action }) => {
+            // A node may already be running from the same config, so stay out of its way
+            let config = args.node_builder(args.config_file()?)?.ephemeral().build()?;
+            return dht_cli::run(config, action).await;
+        }
+        None => {}
+    }
+
+    let file = args.config_file()?;
+    let interactive = args.interactive(file.as_ref());
+    let config = args.node_builder(file)?.build()?;
+
+    // Keep stdout for the console in interactive mode
+    let log_layer = if interactive {
+        fmt::layer().with_writer(std::io::stderr).boxed()
+    } else {
+        fmt::layer().boxed()
+    };
     tracing_subscriber::registry()
-        .with(fmt::layer())
-        .with(EnvFilter::new("info,libp2p=debug"))
+        .with(log_layer)
+        .with(EnvFilter::new(config.log_filter()))
         .init();
-    
-    let args = Args::parse();
-    
+
     info!("🚀 Starting P2P node...");
-    
-    let mut node = P2PNode::new(
-        args.name, 
-        args.port, 
-        args.dht, 
-        args.mdns, 
-        args.bootstrap,
-        args.relay
-    ).await?;
-    
-    if let Some(addr) = args.connect {
-        node.connect_to_peer(&addr).await?;
-    }
-    
+
+    let mut node = P2PNode::new(config).await?;
+
+    let mut events = node.subscribe_events();
+    if interactive {
+        tokio::spawn(console::run(node.handle(), events));
+    } else {
+        // Print messages from application topics
+        tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                if let NodeEvent::MessageReceived { topic, source, sequence_number, data, .. } = event {
+                    let source = source.map_or_else(|| "unknown".to_string(), |peer_id| peer_id.to_string());
+                    let seqno = sequence_number.map_or_else(|| "-".to_string(), |n| n.to_string());
+                    info!("📨 [{}] {} #{}: {}", topic, source, seqno, String::from_utf8_lossy(&data));
+                }
+            }
+        });
+    }
+
+    let handle = node.handle();
+    tokio::spawn(async move {
+        if let Err(e) = shutdown_signal().await {
+            error!("Failed to listen for shutdown signals: {}", e);
+            return;
+        }
+        info!("🛑 Received shutdown signal");
+        if let Err(e) = handle.shutdown().await {
+            error!("Shutdown failed: {}", e);
+        }
+    });
+
     node.run().await
-}
\ No newline at end of file
+}
+
+/// Wait for Ctrl-C or, on Unix, SIGTERM as sent by `docker stop`.
+async fn shutdown_signal() -> Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut sigterm = signal(SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result?,
+            _ = sigterm.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await?;
+
+    Ok(())
+}
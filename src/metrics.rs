@@ -0,0 +1,131 @@
+use anyhow::{Context, Result};
+use axum::{extract::State, http::header, response::IntoResponse, routing::get, Router};
+use libp2p::{
+    metrics::{Metrics, Recorder, Registry},
+    swarm::SwarmEvent,
+};
+use prometheus_client::{
+    encoding::text::encode,
+    metrics::{counter::Counter, gauge::Gauge},
+};
+use std::{net::SocketAddr, sync::Arc};
+use tokio::{sync::oneshot, task::JoinHandle};
+use tracing::{error, info};
+
+use crate::p2p_node::P2PBehaviourEvent;
+
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// libp2p's standard protocol metrics plus counters specific to this node.
+pub(crate) struct NodeMetrics {
+    libp2p: Metrics,
+    pub(crate) handshakes_sent: Counter,
+    pub(crate) handshakes_received: Counter,
+    pub(crate) handshakes_invalid: Counter,
+    pub(crate) bootstrap_dials: Counter,
+    pub(crate) random_walks: Counter,
+    pub(crate) graylisted_peers: Gauge,
+}
+
+impl NodeMetrics {
+    pub(crate) fn new(registry: &mut Registry) -> Self {
+        let libp2p = Metrics::new(registry);
+
+        let registry = registry.sub_registry_with_prefix("node_eeb");
+        let metrics = Self {
+            libp2p,
+            handshakes_sent: Counter::default(),
+            handshakes_received: Counter::default(),
+            handshakes_invalid: Counter::default(),
+            bootstrap_dials: Counter::default(),
+            random_walks: Counter::default(),
+            graylisted_peers: Gauge::default(),
+        };
+        registry.register(
+            "handshakes_sent",
+            "Handshake messages published",
+            metrics.handshakes_sent.clone(),
+        );
+        registry.register(
+            "handshakes_received",
+            "Handshake messages received and parsed",
+            metrics.handshakes_received.clone(),
+        );
+        registry.register(
+            "handshakes_invalid",
+            "Handshake messages rejected by validation",
+            metrics.handshakes_invalid.clone(),
+        );
+        registry.register(
+            "bootstrap_dials",
+            "Dial attempts to configured bootstrap peers",
+            metrics.bootstrap_dials.clone(),
+        );
+        registry.register(
+            "random_walks",
+            "Kademlia random walks started to discover peers",
+            metrics.random_walks.clone(),
+        );
+        registry.register(
+            "graylisted_peers",
+            "Connected peers whose gossipsub score is below the graylist threshold",
+            metrics.graylisted_peers.clone(),
+        );
+        metrics
+    }
+
+    /// Feed a swarm event, and the behaviour event inside it, to libp2p's recorders.
+    pub(crate) fn record(&self, event: &SwarmEvent<P2PBehaviourEvent>) {
+        self.libp2p.record(event);
+
+        let SwarmEvent::Behaviour(event) = event else {
+            return;
+        };
+        match event {
+            P2PBehaviourEvent::Gossipsub(event) => self.libp2p.record(event),
+            P2PBehaviourEvent::Kademlia(event) => self.libp2p.record(event),
+            P2PBehaviourEvent::Identify(event) => self.libp2p.record(event),
+            P2PBehaviourEvent::Ping(event) => self.libp2p.record(event),
+            P2PBehaviourEvent::Relay(event) => self.libp2p.record(event),
+            P2PBehaviourEvent::Dcutr(event) => self.libp2p.record(event),
+            _ => {}
+        }
+    }
+}
+
+/// Bind a Prometheus scrape endpoint at `http://<addr>/metrics` and serve it
+/// in the background until `stop` fires or is dropped.
+pub(crate) async fn spawn(
+    addr: SocketAddr,
+    registry: Arc<Registry>,
+    stop: oneshot::Receiver<()>,
+) -> Result<JoinHandle<()>> {
+    let app = Router::new()
+        .route("/metrics", get(metrics))
+        .with_state(registry);
+
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind metrics endpoint on {}", addr))?;
+    info!("📊 Metrics available at http://{}/metrics", listener.local_addr()?);
+
+    Ok(tokio::spawn(async move {
+        let stop = async {
+            let _ = stop.await;
+        };
+        if let Err(e) = axum::serve(listener, app).with_graceful_shutdown(stop).await {
+            error!("Metrics endpoint stopped: {}", e);
+        }
+    }))
+}
+
+async fn metrics(State(registry): State<Arc<Registry>>) -> impl IntoResponse {
+    let mut body = String::new();
+    match encode(&mut body, &registry) {
+        Ok(()) => ([(header::CONTENT_TYPE, CONTENT_TYPE)], body).into_response(),
+        Err(e) => {
+            error!("Failed to encode metrics: {}", e);
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
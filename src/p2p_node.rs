@@ -1,148 +1,284 @@
 use anyhow::{anyhow, Result};
 use libp2p::{
     autonat,
+    core::{
+        transport::{ListenerId, TransportError},
+        upgrade::NegotiationError,
+    },
     dcutr,
-    gossipsub::{self, IdentTopic, MessageAuthenticity, ValidationMode, MessageId},
+    gossipsub::{self, IdentTopic, MessageAcceptance, MessageAuthenticity, ValidationMode, MessageId, TopicHash},
     identify,
-    kad::{self, store::MemoryStore},
+    kad::{self, store::{MemoryStoreConfig, RecordStore}},
     mdns,
+    metrics::Registry,
     multiaddr::Protocol,
     noise,
     ping,
     relay,
-    swarm::{NetworkBehaviour, SwarmEvent},
-    tcp, yamux, Multiaddr, PeerId, Swarm, Transport,
+    request_response,
+    swarm::{
+        behaviour::toggle::Toggle,
+        dial_opts::{DialOpts, PeerCondition},
+        DialError, ListenError, NetworkBehaviour, SwarmEvent,
+    },
+    Multiaddr, PeerId, Swarm,
 };
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::{
-    collections::hash_map::DefaultHasher,
-    hash::{Hash, Hasher},
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    num::NonZeroUsize,
+    sync::Arc,
     time::Duration,
 };
-use tokio::{select, time::interval};
+use tokio::{
+    select,
+    sync::{broadcast, mpsc, oneshot},
+    task::JoinHandle,
+    time::{interval, timeout, timeout_at, Instant},
+};
 use tracing::{debug, error, info, warn};
 use futures::StreamExt;
 
-const PROTOCOL_VERSION: &str = "/node-eeb/1.0.0";
-const HANDSHAKE_TOPIC: &str = "node-eeb-handshakes";
+use crate::{
+    admin,
+    bootstrap::{self, BootstrapRound},
+    command::{
+        Command, FoundRecord, GetRecordOutcome, ListenAddrs, PeerInfo, PendingQuery, PutRecordOutcome, TopicInfo,
+    },
+    config::{handshake_topic_score_params, websocket_listen_addrs, MessageIdMode, NodeConfig},
+    event::{EventStream, NodeEvent, EVENT_BUFFER},
+    handle::NodeHandle,
+    handshake::{self, HandshakeInfo, SignedHandshake, AGENT_VERSION, HANDSHAKE_PROTOCOL},
+    identity,
+    metrics::{self, NodeMetrics},
+    peer_store::PeerStore,
+    record_store::DhtStore,
+    transport,
+    util::unix_time,
+    validation::{HandshakeValidator, MessageValidator},
+};
+
+/// Pending commands buffered between callers and the swarm loop.
+const COMMAND_BUFFER: usize = 64;
+
+/// Time given to the goodbye message to reach peers before connections are closed.
+const LEAVE_GRACE: Duration = Duration::from_millis(500);
+
+/// How often changes to the peer store and DHT records are written to disk.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often peer scores are checked for graylisting and logged.
+const SCORE_CHECK_INTERVAL: Duration = Duration::from_secs(10);
 
-const BOOTSTRAP_NODES: &[&str] = &[
-    "/dnsaddr/bootstrap.libp2p.io/p2p/QmNnooDu7bfjPFoTZYxMNLWUQJyrVwtbZg5gBMjTezGAJN",
-    "/dnsaddr/bootstrap.libp2p.io/p2p/QmQCU2EcMqAqQPR2i9bChDtGNJchTbq5TbXJJ16u19uLTa", 
-    "/dnsaddr/bootstrap.libp2p.io/p2p/QmbLHAnMoJPWSCR5Zp9Kky4f5RmvJw2e6GrmNw9hxKL1MH",
-];
+/// Smallest score change worth a debug log line.
+const SCORE_LOG_DELTA: f64 = 1.0;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HandshakeMessage {
     pub node_name: Option<String>,
     pub peer_id: String,
     pub timestamp: u64,
     pub message: String,
+    /// Set on the goodbye published when the sender shuts down.
+    #[serde(default)]
+    pub leaving: bool,
 }
 
 #[derive(NetworkBehaviour)]
 pub struct P2PBehaviour {
     gossipsub: gossipsub::Behaviour,
-    mdns: mdns::tokio::Behaviour,
-    kademlia: kad::Behaviour<MemoryStore>,
+    mdns: Toggle<mdns::tokio::Behaviour>,
+    kademlia: Toggle<kad::Behaviour<DhtStore>>,
     identify: identify::Behaviour,
     ping: ping::Behaviour,
-    relay: relay::Behaviour,
+    relay: Toggle<relay::Behaviour>,
+    relay_client: relay::client::Behaviour,
     dcutr: dcutr::Behaviour,
     autonat: autonat::Behaviour,
+    handshake: handshake::Behaviour,
 }
 
 pub struct P2PNode {
     swarm: Swarm<P2PBehaviour>,
+    local_key: libp2p::identity::Keypair,
     node_name: Option<String>,
+    protocol_version: String,
+    capabilities: Vec<String>,
+    peer_handshakes: HashMap<PeerId, HandshakeInfo>,
+    peer_store: Option<PeerStore>,
     handshake_topic: IdentTopic,
+    bootstrap_peers: Vec<Multiaddr>,
+    bootstrap_round: BootstrapRound,
+    handshake_interval: Duration,
+    bootstrap_interval: Duration,
+    listeners: Vec<ListenerId>,
+    relay_listeners: HashMap<ListenerId, Multiaddr>,
+    private_network: bool,
+    connected_addrs: HashMap<PeerId, Vec<Multiaddr>>,
+    admin_listen: Option<SocketAddr>,
+    /// Cloned into handles until `run` starts; dropped then so the channel
+    /// lives only as long as the handles do.
+    command_tx: Option<mpsc::Sender<Command>>,
+    command_rx: mpsc::Receiver<Command>,
+    metrics: NodeMetrics,
+    metrics_registry: Arc<Registry>,
+    metrics_listen: Option<SocketAddr>,
+    /// Admin API and metrics servers, stopped when `run` returns.
+    servers: Vec<(oneshot::Sender<()>, JoinHandle<()>)>,
+    /// `None` once `run` has returned, which ends every event stream.
+    events: Option<broadcast::Sender<NodeEvent>>,
+    pending_queries: HashMap<kad::QueryId, PendingQuery>,
+    /// Configured keys to provide, announced once the routing table has a peer.
+    unannounced_keys: Vec<kad::RecordKey>,
+    dial_providers: bool,
+    shutdown_timeout: Duration,
+    handshake_max_clock_skew: Duration,
+    validators: HashMap<TopicHash, Arc<dyn MessageValidator>>,
+    graylist_threshold: Option<f64>,
+    peer_scores: HashMap<PeerId, f64>,
 }
 
 impl P2PNode {
-    pub async fn new(
-        name: Option<String>,
-        port: Option<u16>,
-        enable_dht: bool,
-        enable_mdns: bool,
-        use_bootstrap: bool,
-        relay_mode: bool,
-    ) -> Result<Self> {
-        // Create a random key pair for this node
-        let local_key = libp2p::identity::Keypair::generate_ed25519();
+    pub async fn new(config: NodeConfig) -> Result<Self> {
+        // Use the configured identity if any, otherwise a random key pair
+        let local_key = match (&config.keypair, &config.identity_file) {
+            (Some(keypair), _) => keypair.clone(),
+            (None, Some(path)) => identity::load_or_generate(path)?,
+            (None, None) => libp2p::identity::Keypair::generate_ed25519(),
+        };
         let local_peer_id = PeerId::from(local_key.public());
         
         info!("🆔 Local peer ID: {}", local_peer_id);
         
-        // Set up transport with noise encryption and yamux multiplexing
-        let transport = tcp::tokio::Transport::default()
-            .upgrade(libp2p::core::upgrade::Version::V1)
-            .authenticate(noise::Config::new(&local_key)
-                .map_err(|e| anyhow!("Failed to create noise config: {}", e))?)
-            .multiplex(yamux::Config::default())
-            .boxed();
+        // Create the relay client so we can dial and listen on /p2p-circuit addresses
+        let (relay_transport, relay_client) = relay::client::new(local_peer_id);
+
+        // Prometheus registry shared by libp2p's and our own metrics
+        let mut registry = Registry::default();
+
+        // Set up QUIC and TCP (noise + yamux) transports
+        let psk = config.swarm_key_file.as_deref().map(transport::load_swarm_key).transpose()?;
+        let transport = transport::build(&local_key, relay_transport, psk, &mut registry)?;
 
         // Create gossipsub configuration
+        let message_id_mode = config.gossipsub.message_id_mode;
+        let content_addressed_topics: HashSet<TopicHash> = config.gossipsub.content_addressed_topics
+            .iter()
+            .map(|topic| IdentTopic::new(topic.clone()).hash())
+            .collect();
         let gossipsub_config = gossipsub::ConfigBuilder::default()
-            .heartbeat_interval(Duration::from_secs(10))
+            .heartbeat_interval(config.gossipsub.heartbeat_interval)
+            .mesh_n(config.gossipsub.mesh_n)
+            .mesh_n_low(config.gossipsub.mesh_n_low)
+            .mesh_n_high(config.gossipsub.mesh_n_high)
+            .max_transmit_size(config.gossipsub.max_transmit_size)
             .validation_mode(ValidationMode::Strict)
-            .message_id_fn(|message| {
-                let mut hasher = DefaultHasher::new();
-                message.data.hash(&mut hasher);
-                MessageId::from(hasher.finish().to_string())
+            // Messages are only forwarded once `validate_message` accepts them
+            .validate_messages()
+            .message_id_fn(move |message| {
+                let mode = if content_addressed_topics.contains(&message.topic) {
+                    MessageIdMode::Content
+                } else {
+                    message_id_mode
+                };
+                message_id(message, mode)
             })
             .build()
             .map_err(|e| anyhow!("Failed to build gossipsub config: {}", e))?;
 
         // Create gossipsub behaviour
-        let gossipsub = gossipsub::Behaviour::new(
+        let mut gossipsub = gossipsub::Behaviour::new_with_metrics(
             MessageAuthenticity::Signed(local_key.clone()),
             gossipsub_config,
+            registry.sub_registry_with_prefix("gossipsub"),
+            gossipsub::MetricsConfig::default(),
         ).map_err(|e| anyhow!("Failed to create gossipsub: {}", e))?;
 
+        // Score peers so those forwarding invalid messages end up graylisted
+        let graylist_threshold = match config.peer_scoring.clone() {
+            Some(scoring) => {
+                let mut params = scoring.params;
+                for (topic, topic_params) in scoring.topics {
+                    params.topics.insert(IdentTopic::new(topic).hash(), topic_params);
+                }
+                params
+                    .topics
+                    .entry(IdentTopic::new(config.handshake_topic.clone()).hash())
+                    .or_insert_with(handshake_topic_score_params);
+                let threshold = scoring.thresholds.graylist_threshold;
+                gossipsub
+                    .with_peer_score(params, scoring.thresholds)
+                    .map_err(|e| anyhow!("Failed to enable peer scoring: {}", e))?;
+                Some(threshold)
+            }
+            None => {
+                info!("🔇 Gossipsub peer scoring disabled");
+                None
+            }
+        };
+
         // Create mDNS behaviour for local network discovery
-        let mdns = if enable_mdns {
-            mdns::tokio::Behaviour::new(mdns::Config::default(), local_peer_id)
-                .map_err(|e| anyhow!("Failed to create mDNS: {}", e))?
+        let mdns = if config.enable_mdns {
+            Some(mdns::tokio::Behaviour::new(mdns::Config::default(), local_peer_id)
+                .map_err(|e| anyhow!("Failed to create mDNS: {}", e))?)
         } else {
-            mdns::tokio::Behaviour::new(
-                mdns::Config {
-                    enable_ipv6: false,
-                    ..Default::default()
-                },
-                local_peer_id,
-            ).map_err(|e| anyhow!("Failed to create mDNS: {}", e))?
+            info!("🔇 mDNS discovery disabled");
+            None
         };
 
         // Create Kademlia DHT for peer discovery
-        let store = MemoryStore::new(local_peer_id);
-        let mut kademlia = if enable_dht {
-            let mut kad = kad::Behaviour::new(local_peer_id, store);
-            kad.set_mode(Some(kad::Mode::Server));
-            kad
+        let mut kademlia = if config.enable_dht {
+            let settings = &config.record_store;
+            let store_config = MemoryStoreConfig {
+                max_records: settings.max_records,
+                max_value_bytes: settings.max_value_bytes,
+                max_providers_per_key: settings.max_providers_per_key,
+                ..MemoryStoreConfig::default()
+            };
+            let store = match &settings.path {
+                Some(path) => DhtStore::open(local_peer_id, store_config, path)?,
+                None => DhtStore::memory(local_peer_id, store_config),
+            };
+            let mut kad_config = kad::Config::default();
+            kad_config
+                .set_record_ttl(Some(settings.record_ttl))
+                .set_publication_interval(Some(settings.republish_interval))
+                .set_provider_record_ttl(Some(settings.provider_ttl))
+                .set_provider_publication_interval(Some(settings.reprovide_interval));
+            let mut kad = kad::Behaviour::with_config(local_peer_id, store, kad_config);
+            kad.set_mode(config.kademlia_mode.as_kad_mode());
+            Some(kad)
         } else {
-            kad::Behaviour::new(local_peer_id, store)
+            info!("🔇 Kademlia DHT disabled");
+            if !config.provided_keys.is_empty() {
+                warn!("⚠️ Not providing {} keys because the DHT is disabled", config.provided_keys.len());
+            }
+            None
         };
 
         // Add bootstrap nodes to Kademlia for global discovery
-        if use_bootstrap && enable_dht {
-            for addr in BOOTSTRAP_NODES {
-                if let Ok(multiaddr) = addr.parse::<Multiaddr>() {
-                    if let Some(Protocol::P2p(peer_id)) = multiaddr.iter().last() {
-                        let peer_id = peer_id.try_into();
-                        if let Ok(peer_id) = peer_id {
-                            kademlia.add_address(&peer_id, multiaddr);
-                            info!("🌐 Added bootstrap node: {}", peer_id);
-                        }
-                    }
+        let bootstrap_peers = if config.use_bootstrap {
+            config.bootstrap_peers.clone()
+        } else {
+            Vec::new()
+        };
+        if let Some(kademlia) = kademlia.as_mut() {
+            for multiaddr in &bootstrap_peers {
+                if let Some(peer_id) = bootstrap::peer_id_of(multiaddr) {
+                    kademlia.add_address(&peer_id, multiaddr.clone());
+                    info!("🌐 Added bootstrap node: {}", peer_id);
                 }
             }
         }
 
-        // Create relay behaviour for NAT traversal
-        let relay = if relay_mode {
-            relay::Behaviour::new(local_peer_id, relay::Config::default())
+        // Create relay server behaviour so other peers can relay through us
+        let relay = if config.relay_server {
+            info!("🔁 Relay server enabled");
+            Some(relay::Behaviour::new(local_peer_id, relay::Config::default()))
         } else {
-            relay::Behaviour::new(local_peer_id, relay::Config::default())
+            None
         };
 
         // Create DCUtR behaviour for hole punching
@@ -153,7 +289,7 @@ impl P2PNode {
 
         // Create identify behaviour
         let identify = identify::Behaviour::new(identify::Config::new(
-            PROTOCOL_VERSION.to_string(),
+            config.protocol_version.clone(),
             local_key.public(),
         ));
 
@@ -163,66 +299,202 @@ impl P2PNode {
         // Combine all behaviours
         let behaviour = P2PBehaviour {
             gossipsub,
-            mdns,
-            kademlia,
+            mdns: mdns.into(),
+            kademlia: kademlia.into(),
             identify,
             ping,
-            relay,
+            relay: relay.into(),
+            relay_client,
             dcutr,
             autonat,
+            handshake: handshake::behaviour(),
         };
 
         // Create swarm with proper config
         let swarm_config = libp2p::swarm::Config::with_tokio_executor();
         let mut swarm = Swarm::new(transport, behaviour, local_peer_id, swarm_config);
 
-        // Listen on specified port or random port
-        let listen_addr = if let Some(port) = port {
-            format!("/ip4/0.0.0.0/tcp/{}", port)
-        } else {
-            "/ip4/0.0.0.0/tcp/0".to_string()
-        };
+        // Listen on the configured addresses, tolerating e.g. hosts without IPv6
+        let mut listen_addrs = config.listen_addrs.clone();
+        if let Some(port) = config.websocket_port {
+            listen_addrs.extend(websocket_listen_addrs(port));
+        }
+        if psk.is_some() {
+            // QUIC is unavailable in a private network
+            let configured = !listen_addrs.is_empty();
+            listen_addrs.retain(|addr| !addr.iter().any(|p| matches!(p, Protocol::QuicV1)));
+            if configured && listen_addrs.is_empty() {
+                return Err(anyhow!("Only QUIC listen addresses are configured, but QUIC is unavailable with a swarm key"));
+            }
+        }
+        let mut listeners = Vec::new();
+        for listen_addr in &listen_addrs {
+            match swarm.listen_on(listen_addr.clone()) {
+                Ok(listener_id) => listeners.push(listener_id),
+                Err(e) => warn!("❌ Failed to listen on {}: {}", listen_addr, e),
+            }
+        }
+        if listeners.is_empty() && !listen_addrs.is_empty() {
+            return Err(anyhow!("Failed to listen on any configured address"));
+        }
 
-        swarm.listen_on(listen_addr.parse()
-            .map_err(|e| anyhow!("Failed to parse listen address: {}", e))?)?;
+        for external_addr in &config.external_addrs {
+            info!("🌍 Announcing external address: {}", external_addr);
+            swarm.add_external_address(external_addr.clone());
+        }
+
+        // Listen through each relay so peers behind NAT can reach us via /p2p-circuit
+        let mut relay_listeners = HashMap::new();
+        for relay_addr in &config.relay_via {
+            let circuit_addr = relay_addr.clone().with(Protocol::P2pCircuit);
+            let listener_id = swarm.listen_on(circuit_addr.clone())?;
+            info!("📡 Requesting relay reservation via {}", relay_addr);
+            relay_listeners.insert(listener_id, circuit_addr);
+        }
+
+        for addr in &config.connect {
+            info!("🔗 Connecting to peer: {}", addr);
+            swarm.dial(addr.clone())?;
+        }
 
         // Subscribe to handshake topic
-        let handshake_topic = IdentTopic::new(HANDSHAKE_TOPIC);
+        let handshake_topic = IdentTopic::new(config.handshake_topic.clone());
         swarm.behaviour_mut().gossipsub.subscribe(&handshake_topic)?;
 
-        info!("🎯 Subscribed to handshake topic: {}", HANDSHAKE_TOPIC);
+        info!("🎯 Subscribed to handshake topic: {}", config.handshake_topic);
+
+        // Handshakes are checked by the built-in validator unless one was configured
+        let mut validators: HashMap<TopicHash, Arc<dyn MessageValidator>> = config.validators
+            .iter()
+            .map(|(topic, validator)| (IdentTopic::new(topic.clone()).hash(), validator.clone()))
+            .collect();
+        validators
+            .entry(handshake_topic.hash())
+            .or_insert_with(|| Arc::new(HandshakeValidator::new(config.handshake_max_clock_skew)));
+
+        for topic in &config.topics {
+            swarm.behaviour_mut().gossipsub.subscribe(&IdentTopic::new(topic.clone()))?;
+            info!("🎯 Subscribed to topic: {}", topic);
+        }
+
+        // Advertised in the handshake so peers know what they can ask of us
+        let mut capabilities = vec!["gossipsub".to_string()];
+        if config.enable_dht {
+            capabilities.push("dht".to_string());
+        }
+        if config.relay_server {
+            capabilities.push("relay".to_string());
+        }
+
+        let metrics = NodeMetrics::new(&mut registry);
+
+        // Channel for requests into the swarm loop, e.g. from the admin API
+        let (command_tx, command_rx) = mpsc::channel(COMMAND_BUFFER);
 
         Ok(Self {
             swarm,
-            node_name: name,
+            local_key,
+            node_name: config.node_name,
+            protocol_version: config.protocol_version,
+            capabilities,
+            peer_handshakes: HashMap::new(),
+            peer_store: config.peer_store_file.as_deref().map(PeerStore::load),
             handshake_topic,
+            bootstrap_peers,
+            bootstrap_round: BootstrapRound::default(),
+            handshake_interval: config.handshake_interval,
+            bootstrap_interval: config.bootstrap_interval,
+            listeners,
+            relay_listeners,
+            private_network: psk.is_some(),
+            connected_addrs: HashMap::new(),
+            admin_listen: config.admin_listen,
+            command_tx: Some(command_tx),
+            command_rx,
+            metrics,
+            metrics_registry: Arc::new(registry),
+            metrics_listen: config.metrics_listen,
+            servers: Vec::new(),
+            events: Some(broadcast::channel(EVENT_BUFFER).0),
+            pending_queries: HashMap::new(),
+            unannounced_keys: if config.enable_dht {
+                config.provided_keys.iter().map(kad::RecordKey::new).collect()
+            } else {
+                Vec::new()
+            },
+            dial_providers: config.dial_providers,
+            shutdown_timeout: config.shutdown_timeout,
+            handshake_max_clock_skew: config.handshake_max_clock_skew,
+            validators,
+            graylist_threshold,
+            peer_scores: HashMap::new(),
         })
     }
 
     pub async fn bootstrap_global_network(&mut self) -> Result<()> {
         info!("🌐 Bootstrapping global network...");
         
-        // Try to connect to bootstrap nodes
-        for addr in BOOTSTRAP_NODES {
-            if let Ok(multiaddr) = addr.parse::<Multiaddr>() {
-                info!("🔗 Connecting to bootstrap node: {}", multiaddr);
-                if let Err(e) = self.swarm.dial(multiaddr.clone()) {
-                    debug!("Failed to dial bootstrap node {}: {}", multiaddr, e);
-                }
+        if self.bootstrap_peers.is_empty() {
+            info!("🌐 No bootstrap peers configured");
+        }
+        
+        // Try to connect to bootstrap nodes; results are summarised once all dials finish
+        for multiaddr in self.bootstrap_peers.clone() {
+            let Some(peer_id) = bootstrap::peer_id_of(&multiaddr) else {
+                continue;
+            };
+            
+            info!("🔗 Connecting to bootstrap node: {}", multiaddr);
+            self.bootstrap_round.dialing(peer_id, multiaddr.clone());
+            self.metrics.bootstrap_dials.inc();
+            
+            let opts = DialOpts::peer_id(peer_id)
+                .addresses(vec![multiaddr])
+                .condition(PeerCondition::Disconnected)
+                .build();
+            match self.swarm.dial(opts) {
+                Ok(()) => {}
+                Err(DialError::DialPeerConditionFalse(_)) => self.bootstrap_round.succeeded(&peer_id),
+                Err(e) => self.bootstrap_round.failed(&peer_id, e),
             }
         }
 
         // Start Kademlia bootstrap process
-        if let Err(e) = self.swarm.behaviour_mut().kademlia.bootstrap() {
-            debug!("Kademlia bootstrap failed: {}", e);
+        if let Some(kademlia) = self.kademlia() {
+            if let Err(e) = kademlia.bootstrap() {
+                debug!("Kademlia bootstrap failed: {}", e);
+            }
         }
 
         Ok(())
     }
 
+    /// Subscribe to events from the node. Subscribe before calling [`run`](Self::run)
+    /// to see everything from startup on.
+    pub fn subscribe_events(&self) -> EventStream {
+        let receiver = match &self.events {
+            Some(events) => events.subscribe(),
+            // The sender is dropped right away, so the stream ends immediately
+            None => broadcast::channel(1).0.subscribe(),
+        };
+        EventStream::new(receiver)
+    }
+
+    /// Handle for driving the node from other tasks while [`run`](Self::run) owns it.
+    pub fn handle(&self) -> NodeHandle {
+        let commands = match &self.command_tx {
+            Some(commands) => commands.clone(),
+            // Nothing receives on this channel, so every call fails with NodeStopped
+            None => mpsc::channel(1).0,
+        };
+        NodeHandle::new(commands, *self.swarm.local_peer_id())
+    }
+
     pub async fn connect_to_peer(&mut self, addr: &str) -> Result<()> {
-        let multiaddr: Multiaddr = addr.parse()?;
-        
+        self.dial(addr.parse()?)
+    }
+
+    fn dial(&mut self, multiaddr: Multiaddr) -> Result<()> {
         // Extract peer ID from multiaddr if present
         if let Some(Protocol::P2p(peer_id)) = multiaddr.iter().last() {
             info!("🔗 Connecting to peer: {} at {}", peer_id, multiaddr);
@@ -234,51 +506,109 @@ impl P2PNode {
         Ok(())
     }
 
+    /// Drive the node until it is shut down through a [`NodeHandle`].
+    ///
+    /// Once this returns, handle calls fail with [`NodeStopped`](crate::NodeStopped)
+    /// and event streams end, even while the node itself is still around.
     pub async fn run(&mut self) -> Result<()> {
+        let result = self.run_until_shutdown().await;
+        self.close_channels();
+        self.stop_servers().await;
+        result
+    }
+
+    /// Stop the admin API and metrics endpoint so their ports are free once
+    /// `run` returns. Requests still in flight get the shutdown timeout to finish.
+    async fn stop_servers(&mut self) {
+        for (stop, server) in self.servers.drain(..) {
+            let _ = stop.send(());
+            if timeout(self.shutdown_timeout, server).await.is_err() {
+                warn!("⏱️ HTTP server did not stop within {:?}", self.shutdown_timeout);
+            }
+        }
+    }
+
+    async fn run_until_shutdown(&mut self) -> Result<()> {
         info!("🌐 P2P node is running and ready to connect!");
         
-        // Bootstrap the global network
+        if let Some(addr) = self.admin_listen {
+            let (stop, stopped) = oneshot::channel();
+            let server = admin::spawn(addr, self.handle(), stopped).await?;
+            self.servers.push((stop, server));
+        }
+        self.command_tx = None;
+        if let Some(addr) = self.metrics_listen {
+            let (stop, stopped) = oneshot::channel();
+            let server = metrics::spawn(addr, self.metrics_registry.clone(), stopped).await?;
+            self.servers.push((stop, server));
+        }
+        
+        // Peers from earlier sessions first, then the bootstrap peers
+        self.redial_known_peers();
         self.bootstrap_global_network().await?;
         
-        let mut handshake_interval = interval(Duration::from_secs(30));
-        let mut bootstrap_interval = interval(Duration::from_secs(300)); // Re-bootstrap every 5 minutes
+        let mut handshake_interval = interval(self.handshake_interval);
+        let mut bootstrap_interval = interval(self.bootstrap_interval);
+        let mut score_interval = interval(SCORE_CHECK_INTERVAL);
+        let mut flush_interval = interval(FLUSH_INTERVAL);
         
         loop {
             select! {
                 event = self.swarm.next() => {
                     if let Some(event) = event {
+                        self.metrics.record(&event);
+                        
                         match event {
                             SwarmEvent::Behaviour(P2PBehaviourEvent::Autonat(autonat::Event::StatusChanged { old, new })) => {
                                 info!("🔍 NAT status changed from {:?} to {:?}", old, new);
+                                self.emit(NodeEvent::NatStatusChanged { old, new });
                             }
                             
                             SwarmEvent::Behaviour(P2PBehaviourEvent::Dcutr(event)) => {
-                                match event {
-                                    dcutr::Event::InitiatedDirectConnectionUpgrade { remote_peer_id, .. } => {
-                                        info!("🔄 Initiated direct connection upgrade to {}", remote_peer_id);
+                                let result = match event.result {
+                                    Ok(_) => {
+                                        info!("✅ Direct connection upgrade succeeded with {}", event.remote_peer_id);
+                                        Ok(())
                                     }
-                                    dcutr::Event::RemoteInitiatedDirectConnectionUpgrade { remote_peer_id, .. } => {
-                                        info!("🔄 Remote initiated direct connection upgrade from {}", remote_peer_id);
-                                    }
-                                    dcutr::Event::DirectConnectionUpgradeSucceeded { remote_peer_id } => {
-                                        info!("✅ Direct connection upgrade succeeded with {}", remote_peer_id);
-                                    }
-                                    dcutr::Event::DirectConnectionUpgradeFailed { remote_peer_id, error } => {
-                                        warn!("❌ Direct connection upgrade failed with {}: {}", remote_peer_id, error);
+                                    Err(e) => {
+                                        warn!("❌ Direct connection upgrade failed with {}: {}", event.remote_peer_id, e);
+                                        Err(e.to_string())
                                     }
-                                }
+                                };
+                                self.emit(NodeEvent::HolePunchResult { peer_id: event.remote_peer_id, result });
                             }
                             
                             SwarmEvent::Behaviour(P2PBehaviourEvent::Relay(relay::Event::ReservationReqAccepted { src_peer_id, .. })) => {
                                 info!("🔗 Relay reservation accepted by {}", src_peer_id);
                             }
                             
+                            SwarmEvent::Behaviour(P2PBehaviourEvent::RelayClient(event)) => {
+                                match event {
+                                    relay::client::Event::ReservationReqAccepted { relay_peer_id, renewal, .. } => {
+                                        if renewal {
+                                            debug!("📡 Relay reservation renewed with {}", relay_peer_id);
+                                        } else {
+                                            info!("📡 Relay reservation accepted by {}", relay_peer_id);
+                                        }
+                                        self.emit(NodeEvent::RelayReservation { relay_peer_id, renewal });
+                                    }
+                                    relay::client::Event::OutboundCircuitEstablished { relay_peer_id, .. } => {
+                                        info!("🔀 Outbound relay circuit established via {}", relay_peer_id);
+                                    }
+                                    relay::client::Event::InboundCircuitEstablished { src_peer_id, .. } => {
+                                        info!("🔀 Inbound relay circuit established from {}", src_peer_id);
+                                    }
+                                }
+                            }
+                            
                             SwarmEvent::Behaviour(P2PBehaviourEvent::Mdns(mdns::Event::Discovered(list))) => {
                                 for (peer_id, multiaddr) in list {
                                     info!("🔍 mDNS discovered peer: {} at {}", peer_id, multiaddr);
                                     
                                     // Add to Kademlia routing table
-                                    self.swarm.behaviour_mut().kademlia.add_address(&peer_id, multiaddr.clone());
+                                    if let Some(kademlia) = self.kademlia() {
+                                        kademlia.add_address(&peer_id, multiaddr.clone());
+                                    }
                                     
                                     // Try to connect
                                     if let Err(e) = self.swarm.dial(multiaddr.clone()) {
@@ -294,13 +624,15 @@ impl P2PNode {
                             }
                             
                             SwarmEvent::Behaviour(P2PBehaviourEvent::Gossipsub(gossipsub::Event::Message {
-                                propagation_source: peer_id,
+                                propagation_source,
+                                message_id,
                                 message,
-                                ..
                             })) => {
-                                if message.topic == self.handshake_topic.hash() {
-                                    self.handle_handshake_message(peer_id, &message.data).await;
-                                }
+                                self.handle_gossip_message(propagation_source, message_id, message).await;
+                            }
+                            
+                            SwarmEvent::Behaviour(P2PBehaviourEvent::Handshake(event)) => {
+                                self.handle_handshake_event(event);
                             }
                             
                             SwarmEvent::Behaviour(P2PBehaviourEvent::Identify(identify::Event::Received {
@@ -310,9 +642,45 @@ impl P2PNode {
                                 info!("🆔 Identified peer: {} with protocol {}", peer_id, info.protocol_version);
                                 
                                 // Add addresses to Kademlia
-                                for addr in info.listen_addrs {
-                                    self.swarm.behaviour_mut().kademlia.add_address(&peer_id, addr);
+                                if let Some(kademlia) = self.kademlia() {
+                                    for addr in &info.listen_addrs {
+                                        kademlia.add_address(&peer_id, addr.clone());
+                                    }
                                 }
+                                if let Some(store) = &mut self.peer_store {
+                                    store.identified(peer_id, &info.agent_version, &info.protocol_version, &info.listen_addrs);
+                                }
+                                self.emit(NodeEvent::PeerIdentified {
+                                    peer_id,
+                                    protocol_version: info.protocol_version,
+                                    agent_version: info.agent_version,
+                                    listen_addrs: info.listen_addrs,
+                                    protocols: info.protocols.iter().map(|p| p.to_string()).collect(),
+                                });
+                            }
+                            
+                            SwarmEvent::Behaviour(P2PBehaviourEvent::Kademlia(kad::Event::OutboundQueryProgressed {
+                                id,
+                                result,
+                                step,
+                                ..
+                            })) if self.pending_queries.contains_key(&id) => {
+                                self.handle_query_result(id, result, step.last);
+                            }
+                            
+                            SwarmEvent::Behaviour(P2PBehaviourEvent::Kademlia(kad::Event::RoutingUpdated {
+                                is_new_peer: true,
+                                ..
+                            })) if !self.unannounced_keys.is_empty() => {
+                                self.announce_provided_keys();
+                            }
+                            
+                            SwarmEvent::Behaviour(P2PBehaviourEvent::Kademlia(kad::Event::OutboundQueryProgressed {
+                                result: kad::QueryResult::StartProviding(result) | kad::QueryResult::RepublishProvider(result),
+                                ..
+                            })) => {
+                                // Nobody waits on these, the outcome is logged and emitted
+                                let _ = self.provider_announced(result);
                             }
                             
                             SwarmEvent::Behaviour(P2PBehaviourEvent::Kademlia(kad::Event::OutboundQueryProgressed {
@@ -323,7 +691,7 @@ impl P2PNode {
                             }
                             
                             SwarmEvent::Behaviour(P2PBehaviourEvent::Kademlia(kad::Event::OutboundQueryProgressed {
-                                result: kad::QueryResult::GetClosestPeers(Ok(kad::GetClosestPeersOk { key, peers, .. })),
+                                result: kad::QueryResult::GetClosestPeers(Ok(kad::GetClosestPeersOk { peers, .. })),
                                 ..
                             })) => {
                                 info!("🔍 Found {} peers close to key", peers.len());
@@ -342,6 +710,9 @@ impl P2PNode {
                                 match event.result {
                                     Ok(rtt) => {
                                         debug!("🏓 Ping to {} successful: {:?}", event.peer, rtt);
+                                        if let Some(store) = &mut self.peer_store {
+                                            store.pinged(event.peer, rtt);
+                                        }
                                     }
                                     Err(e) => {
                                         debug!("🏓 Ping to {} failed: {}", event.peer, e);
@@ -350,26 +721,56 @@ impl P2PNode {
                             }
                             
                             SwarmEvent::NewListenAddr { address, .. } => {
-                                let local_peer_id = *self.swarm.local_peer_id();
-                                info!("🎧 Listening on: {}/p2p/{}", address, local_peer_id);
+                                // Relayed addresses already end in our peer ID
+                                let address = if matches!(address.iter().last(), Some(Protocol::P2p(_))) {
+                                    address
+                                } else {
+                                    address.with(Protocol::P2p(*self.swarm.local_peer_id()))
+                                };
+                                info!("🎧 Listening on: {}", address);
+                                self.emit(NodeEvent::ListenAddrAdded { address });
                                 
                                 // Bootstrap the DHT after we start listening
-                                if let Err(e) = self.swarm.behaviour_mut().kademlia.bootstrap() {
-                                    debug!("Failed to bootstrap Kademlia: {}", e);
+                                if let Some(kademlia) = self.kademlia() {
+                                    if let Err(e) = kademlia.bootstrap() {
+                                        debug!("Failed to bootstrap Kademlia: {}", e);
+                                    }
+                                    
+                                    // Start random walk to discover peers
+                                    kademlia.get_closest_peers(PeerId::random());
+                                    self.metrics.random_walks.inc();
                                 }
-                                
-                                // Start random walk to discover peers
-                                let random_peer_id = PeerId::random();
-                                self.swarm.behaviour_mut().kademlia.get_closest_peers(random_peer_id);
                             }
                             
-                            SwarmEvent::ConnectionEstablished { peer_id, .. } => {
+                            SwarmEvent::ConnectionEstablished { peer_id, endpoint, num_established, .. } => {
                                 info!("🤝 Connected to peer: {}", peer_id);
-                                self.send_handshake_message(peer_id).await;
+                                let address = endpoint.get_remote_address().clone();
+                                self.connected_addrs.entry(peer_id).or_default().push(address.clone());
+                                if let Some(store) = &mut self.peer_store {
+                                    store.connected(peer_id, endpoint.is_dialer().then_some(&address), unix_time());
+                                }
+                                // When both sides dial at once, each may see the other's connection
+                                // first, so any of our dials starts the handshake until it completed
+                                if endpoint.is_dialer() && !self.peer_handshakes.contains_key(&peer_id) {
+                                    self.start_handshake(peer_id);
+                                }
+                                if num_established.get() == 1 {
+                                    self.emit(NodeEvent::PeerConnected { peer_id, address });
+                                }
+                                self.bootstrap_round.succeeded(&peer_id);
                             }
                             
-                            SwarmEvent::ConnectionClosed { peer_id, .. } => {
-                                info!("👋 Disconnected from peer: {}", peer_id);
+                            SwarmEvent::ConnectionClosed { peer_id, endpoint, num_established, .. } => {
+                                if num_established == 0 {
+                                    info!("👋 Disconnected from peer: {}", peer_id);
+                                    self.connected_addrs.remove(&peer_id);
+                                    self.peer_handshakes.remove(&peer_id);
+                                    self.emit(NodeEvent::PeerDisconnected { peer_id });
+                                } else if let Some(addrs) = self.connected_addrs.get_mut(&peer_id) {
+                                    if let Some(pos) = addrs.iter().position(|a| a == endpoint.get_remote_address()) {
+                                        addrs.remove(pos);
+                                    }
+                                }
                             }
                             
                             SwarmEvent::IncomingConnection { .. } => {
@@ -379,13 +780,48 @@ impl P2PNode {
                             SwarmEvent::OutgoingConnectionError { peer_id, error, .. } => {
                                 if let Some(peer_id) = peer_id {
                                     warn!("❌ Outgoing connection error to {}: {}", peer_id, error);
+                                    self.bootstrap_round.failed(&peer_id, &error);
+                                    if let Some(store) = &mut self.peer_store {
+                                        store.dial_failed(&peer_id);
+                                    }
                                 } else {
                                     warn!("❌ Outgoing connection error: {}", error);
                                 }
+                                
+                                if self.private_network {
+                                    if let DialError::Transport(errors) = &error {
+                                        for (addr, _) in errors.iter().filter(|(_, e)| is_handshake_failure(e)) {
+                                            warn!("🔒 Private network handshake with {} failed; the peer may use a different swarm key", addr);
+                                        }
+                                    }
+                                }
                             }
                             
-                            SwarmEvent::IncomingConnectionError { error, .. } => {
+                            SwarmEvent::IncomingConnectionError { send_back_addr, error, .. } => {
                                 warn!("❌ Incoming connection error: {}", error);
+                                
+                                if self.private_network {
+                                    if let ListenError::Transport(e) = &error {
+                                        if is_handshake_failure(e) {
+                                            warn!("🔒 Private network handshake with {} failed; the peer may use a different swarm key", send_back_addr);
+                                        }
+                                    }
+                                }
+                            }
+                            
+                            SwarmEvent::ListenerClosed { listener_id, reason, .. } => {
+                                if let Some(circuit_addr) = self.relay_listeners.remove(&listener_id) {
+                                    match reason {
+                                        Ok(()) => warn!("📡 Relay reservation on {} closed", circuit_addr),
+                                        Err(e) => warn!("📡 Relay reservation on {} failed: {}", circuit_addr, e),
+                                    }
+                                }
+                            }
+                            
+                            SwarmEvent::ListenerError { listener_id, error } => {
+                                if let Some(circuit_addr) = self.relay_listeners.get(&listener_id) {
+                                    warn!("📡 Relay listener on {} error: {}", circuit_addr, error);
+                                }
                             }
                             
                             _ => {}
@@ -393,52 +829,709 @@ impl P2PNode {
                     }
                 }
                 
+                Some(command) = self.command_rx.recv() => {
+                    if let Command::Shutdown { reply } = command {
+                        self.shutdown(reply).await;
+                        return Ok(());
+                    }
+                    self.handle_command(command);
+                }
+                
                 _ = handshake_interval.tick() => {
                     self.broadcast_handshake().await;
                 }
                 
                 _ = bootstrap_interval.tick() => {
                     // Periodically re-bootstrap and discover new peers
-                    info!("🔄 Periodic network discovery...");
-                    if let Err(e) = self.swarm.behaviour_mut().kademlia.bootstrap() {
-                        debug!("Periodic bootstrap failed: {}", e);
+                    if let Some(kademlia) = self.kademlia() {
+                        info!("🔄 Periodic network discovery...");
+                        if let Err(e) = kademlia.bootstrap() {
+                            debug!("Periodic bootstrap failed: {}", e);
+                        }
+                        
+                        // Random walk to find new peers
+                        kademlia.get_closest_peers(PeerId::random());
+                        self.metrics.random_walks.inc();
                     }
-                    
-                    // Random walk to find new peers
-                    let random_peer_id = PeerId::random();
-                    self.swarm.behaviour_mut().kademlia.get_closest_peers(random_peer_id);
                 }
+
+                _ = score_interval.tick(), if self.graylist_threshold.is_some() => {
+                    self.check_peer_scores();
+                }
+
+                _ = flush_interval.tick() => {
+                    self.flush_stores();
+                }
+            }
+        }
+    }
+
+    /// Dial peers remembered from earlier sessions, which usually get us back
+    /// into the network faster than the bootstrap peers.
+    fn redial_known_peers(&mut self) {
+        let Some(store) = &self.peer_store else {
+            return;
+        };
+        let candidates = store.redial_candidates();
+        if candidates.is_empty() {
+            return;
+        }
+
+        info!("📒 Redialing {} known peers", candidates.len());
+        for (peer_id, addresses) in candidates {
+            if let Some(kademlia) = self.kademlia() {
+                for addr in &addresses {
+                    kademlia.add_address(&peer_id, addr.clone());
+                }
+            }
+            let opts = DialOpts::peer_id(peer_id)
+                .addresses(addresses)
+                .condition(PeerCondition::DisconnectedAndNotDialing)
+                .build();
+            if let Err(e) = self.swarm.dial(opts) {
+                debug!("Failed to redial {}: {}", peer_id, e);
+            }
+        }
+    }
+
+    /// Write the peer store and DHT records to disk if they changed.
+    fn flush_stores(&mut self) {
+        if let Some(store) = &mut self.peer_store {
+            if let Err(e) = store.flush() {
+                warn!("⚠️ Failed to save peer store: {:#}", e);
+            }
+        }
+        if let Some(kademlia) = self.kademlia() {
+            if let Err(e) = kademlia.store_mut().flush() {
+                warn!("⚠️ Failed to save DHT records: {:#}", e);
             }
         }
     }
 
-    async fn send_handshake_message(&mut self, peer_id: PeerId) {
-        let handshake = HandshakeMessage {
+    /// Log how gossipsub scores of connected peers changed since the last
+    /// check, warning when a peer crosses the graylist threshold.
+    fn check_peer_scores(&mut self) {
+        let Some(graylist_threshold) = self.graylist_threshold else {
+            return;
+        };
+        let gossipsub = &self.swarm.behaviour().gossipsub;
+        let scores: HashMap<PeerId, f64> = self
+            .swarm
+            .connected_peers()
+            .filter_map(|peer_id| gossipsub.peer_score(peer_id).map(|score| (*peer_id, score)))
+            .collect();
+
+        for (peer_id, &score) in &scores {
+            let previous = self.peer_scores.get(peer_id).copied().unwrap_or(0.0);
+            if score < graylist_threshold && previous >= graylist_threshold {
+                warn!("🚫 Graylisted peer {} (score {:.2} < {:.2})", peer_id, score, graylist_threshold);
+            } else if score >= graylist_threshold && previous < graylist_threshold {
+                info!("✅ Peer {} left the graylist (score {:.2})", peer_id, score);
+            } else if (score - previous).abs() >= SCORE_LOG_DELTA {
+                debug!("📈 Peer {} score {:.2} -> {:.2}", peer_id, previous, score);
+            }
+        }
+
+        let graylisted = scores.values().filter(|&&score| score < graylist_threshold).count();
+        self.metrics.graylisted_peers.set(graylisted as i64);
+        self.peer_scores = scores;
+    }
+
+    /// Leave the network cleanly: stop accepting connections, tell peers we
+    /// are leaving and close every connection, giving up once the configured
+    /// shutdown timeout has passed. `reply`, and any other shutdown request
+    /// queued meanwhile, is answered once the node has stopped.
+    async fn shutdown(&mut self, reply: oneshot::Sender<()>) {
+        info!("🛑 Shutting down...");
+        let deadline = Instant::now() + self.shutdown_timeout;
+
+        // Refuse new commands and fail queued ones and running DHT queries
+        // with NodeStopped by dropping their reply senders
+        self.command_rx.close();
+        let mut stopped = vec![reply];
+        while let Ok(command) = self.command_rx.try_recv() {
+            if let Command::Shutdown { reply } = command {
+                stopped.push(reply);
+            }
+        }
+        self.pending_queries.clear();
+
+        for listener_id in self.listeners.drain(..).chain(self.relay_listeners.drain().map(|(id, _)| id)) {
+            self.swarm.remove_listener(listener_id);
+        }
+
+        // Published through gossipsub, so the goodbye is signed with our key like any handshake
+        let goodbye = HandshakeMessage {
             node_name: self.node_name.clone(),
             peer_id: self.swarm.local_peer_id().to_string(),
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+            timestamp: unix_time(),
             message: format!(
-                "Hello from {}! 👋",
+                "{} is leaving the network",
                 self.node_name.as_deref().unwrap_or("Anonymous Node")
             ),
+            leaving: true,
         };
-
-        if let Ok(message_json) = serde_json::to_string(&handshake) {
-            if let Err(e) = self.swarm
+        if let Ok(message_json) = serde_json::to_string(&goodbye) {
+            match self.swarm
                 .behaviour_mut()
                 .gossipsub
                 .publish(self.handshake_topic.clone(), message_json.as_bytes())
             {
-                error!("Failed to publish handshake message: {}", e);
-            } else {
-                info!("📤 Sent handshake to {}", peer_id);
+                Ok(_) => {
+                    info!("📤 Announced departure to peers");
+                    self.metrics.handshakes_sent.inc();
+                }
+                Err(e) => debug!("Could not announce departure: {}", e),
+            }
+        }
+
+        // Keep the swarm going briefly so the goodbye actually leaves
+        let grace = deadline.min(Instant::now() + LEAVE_GRACE);
+        let _ = timeout_at(grace, async {
+            loop {
+                self.swarm.next().await;
+            }
+        })
+        .await;
+
+        let peers: Vec<PeerId> = self.swarm.connected_peers().cloned().collect();
+        for peer_id in peers {
+            let _ = self.swarm.disconnect_peer_id(peer_id);
+        }
+        let closed = timeout_at(deadline, async {
+            while self.swarm.network_info().num_peers() > 0 {
+                self.swarm.next().await;
+            }
+        })
+        .await;
+        if closed.is_err() {
+            warn!(
+                "⏱️ Shutdown timed out, dropping {} remaining connections",
+                self.swarm.network_info().num_peers()
+            );
+        }
+
+        self.flush_stores();
+        info!("👋 Node stopped");
+        for reply in stopped {
+            let _ = reply.send(());
+        }
+    }
+
+    fn handle_command(&mut self, command: Command) {
+        // Replies are dropped silently if the requester went away
+        match command {
+            Command::ConnectedPeers { reply } => {
+                let peers = self.swarm
+                    .connected_peers()
+                    .map(|peer_id| PeerInfo {
+                        peer_id: *peer_id,
+                        addresses: self.connected_addrs.get(peer_id).cloned().unwrap_or_default(),
+                        handshake: self.peer_handshakes.get(peer_id).cloned(),
+                    })
+                    .collect();
+                let _ = reply.send(peers);
+            }
+            Command::ListenAddrs { reply } => {
+                let _ = reply.send(ListenAddrs {
+                    listen: self.swarm.listeners().cloned().collect(),
+                    external: self.swarm.external_addresses().cloned().collect(),
+                });
+            }
+            Command::Topics { reply } => {
+                let gossipsub = &self.swarm.behaviour().gossipsub;
+                let topics = gossipsub
+                    .topics()
+                    .map(|hash| TopicInfo {
+                        topic: hash.to_string(),
+                        mesh_peers: gossipsub.mesh_peers(hash).count(),
+                    })
+                    .collect();
+                let _ = reply.send(topics);
+            }
+            Command::RoutingTable { reply } => {
+                let mut entries = Vec::new();
+                // Borrow the field directly so the handshakes stay readable
+                if let Some(kademlia) = self.swarm.behaviour_mut().kademlia.as_mut() {
+                    for bucket in kademlia.kbuckets() {
+                        for entry in bucket.iter() {
+                            let peer_id = *entry.node.key.preimage();
+                            entries.push(PeerInfo {
+                                peer_id,
+                                addresses: entry.node.value.iter().cloned().collect(),
+                                handshake: self.peer_handshakes.get(&peer_id).cloned(),
+                            });
+                        }
+                    }
+                }
+                let _ = reply.send(entries);
+            }
+            Command::Dial { addr, reply } => {
+                let _ = reply.send(self.dial(addr));
+            }
+            Command::Publish { topic, data, reply } => {
+                let result = self.swarm
+                    .behaviour_mut()
+                    .gossipsub
+                    .publish(IdentTopic::new(topic), data)
+                    .map(|id| id.to_string())
+                    .map_err(|e| anyhow!("Failed to publish: {}", e));
+                let _ = reply.send(result);
+            }
+            Command::Subscribe { topic, reply } => {
+                let result = self.swarm
+                    .behaviour_mut()
+                    .gossipsub
+                    .subscribe(&IdentTopic::new(topic))
+                    .map_err(|e| anyhow!("Failed to subscribe: {}", e));
+                let _ = reply.send(result);
+            }
+            Command::Unsubscribe { topic, reply } => {
+                let result = self.swarm
+                    .behaviour_mut()
+                    .gossipsub
+                    .unsubscribe(&IdentTopic::new(topic))
+                    .map_err(|e| anyhow!("Failed to unsubscribe: {}", e));
+                let _ = reply.send(result);
+            }
+            Command::Disconnect { peer_id, reply } => {
+                let result = self.swarm
+                    .disconnect_peer_id(peer_id)
+                    .map_err(|_| anyhow!("Not connected to {}", peer_id));
+                let _ = reply.send(result);
+            }
+            Command::GetClosestPeers { key, reply } => {
+                let Some(kademlia) = self.kademlia() else {
+                    let _ = reply.send(Err(anyhow!("DHT is disabled")));
+                    return;
+                };
+                let id = kademlia.get_closest_peers(key);
+                self.pending_queries.insert(id, PendingQuery::ClosestPeers(reply));
+            }
+            Command::PutRecord { key, value, options, reply } => {
+                let local_peer_id = *self.swarm.local_peer_id();
+                let Some(kademlia) = self.kademlia() else {
+                    let _ = reply.send(Err(anyhow!("DHT is disabled")));
+                    return;
+                };
+                let mut record = kad::Record::new(kad::RecordKey::new(&key), value);
+                record.expires = options.ttl.map(|ttl| Instant::now().into_std() + ttl);
+                match options.publisher.filter(|&p| p != local_peer_id) {
+                    // put_record always publishes under our own ID, so a record on behalf
+                    // of another peer is kept here and sent once the closest peers are found
+                    Some(publisher) => {
+                        record.publisher = Some(publisher);
+                        match kademlia.store_mut().put(record.clone()) {
+                            Ok(()) => {
+                                let id = kademlia.get_closest_peers(record.key.to_vec());
+                                self.pending_queries.insert(id, PendingQuery::PutRecordAs {
+                                    reply,
+                                    record,
+                                    quorum: options.quorum,
+                                });
+                            }
+                            Err(e) => {
+                                let _ = reply.send(Err(anyhow!("Failed to store record: {:?}", e)));
+                            }
+                        }
+                    }
+                    None => match kademlia.put_record(record, options.quorum.as_kad_quorum()) {
+                        Ok(id) => {
+                            self.pending_queries.insert(id, PendingQuery::PutRecord(reply));
+                        }
+                        Err(e) => {
+                            let _ = reply.send(Err(anyhow!("Failed to store record: {:?}", e)));
+                        }
+                    },
+                }
+            }
+            Command::GetRecord { key, quorum, reply } => {
+                let Some(kademlia) = self.kademlia() else {
+                    let _ = reply.send(Err(anyhow!("DHT is disabled")));
+                    return;
+                };
+                let id = kademlia.get_record(kad::RecordKey::new(&key));
+                self.pending_queries.insert(id, PendingQuery::GetRecord {
+                    reply,
+                    quorum: quorum.required(kad::K_VALUE.get()),
+                    records: Vec::new(),
+                });
+            }
+            Command::StartProviding { key, reply } => {
+                let Some(kademlia) = self.kademlia() else {
+                    let _ = reply.send(Err(anyhow!("DHT is disabled")));
+                    return;
+                };
+                match kademlia.start_providing(kad::RecordKey::new(&key)) {
+                    Ok(id) => {
+                        self.pending_queries.insert(id, PendingQuery::StartProviding(reply));
+                    }
+                    Err(e) => {
+                        let _ = reply.send(Err(anyhow!("Failed to store provider record: {:?}", e)));
+                    }
+                }
+            }
+            Command::StopProviding { key, reply } => {
+                let Some(kademlia) = self.kademlia() else {
+                    let _ = reply.send(Err(anyhow!("DHT is disabled")));
+                    return;
+                };
+                kademlia.stop_providing(&kad::RecordKey::new(&key));
+                info!("📦 Stopped providing {}", String::from_utf8_lossy(&key));
+                let _ = reply.send(Ok(()));
+            }
+            Command::GetProviders { key, reply } => {
+                let Some(kademlia) = self.kademlia() else {
+                    let _ = reply.send(Err(anyhow!("DHT is disabled")));
+                    return;
+                };
+                let id = kademlia.get_providers(kad::RecordKey::new(&key));
+                self.pending_queries.insert(id, PendingQuery::GetProviders { reply, providers: Vec::new() });
+            }
+            Command::Shutdown { .. } => unreachable!("handled by run"),
+        }
+    }
+
+    async fn handle_gossip_message(
+        &mut self,
+        propagation_source: PeerId,
+        message_id: MessageId,
+        message: gossipsub::Message,
+    ) {
+        // Rejected and ignored messages are dropped without forwarding
+        if !self.validate_message(&message_id, &propagation_source, &message) {
+            return;
+        }
+
+        if message.topic == self.handshake_topic.hash() {
+            self.handle_handshake_message(&message).await;
+        } else {
+            debug!("📨 Message {} on {} from {}", message_id, message.topic, propagation_source);
+            self.emit(NodeEvent::MessageReceived {
+                topic: message.topic.into_string(),
+                source: message.source,
+                propagation_source,
+                sequence_number: message.sequence_number,
+                message_id: message_id.to_string(),
+                data: message.data,
+            });
+        }
+    }
+
+    /// Run the validator registered for the message's topic and report the
+    /// verdict to gossipsub, which only forwards accepted messages.
+    /// Returns whether the message should be handled.
+    fn validate_message(
+        &mut self,
+        message_id: &MessageId,
+        propagation_source: &PeerId,
+        message: &gossipsub::Message,
+    ) -> bool {
+        let acceptance = match self.validators.get(&message.topic) {
+            Some(validator) => validator.validate(message),
+            None => MessageAcceptance::Accept,
+        };
+        let accepted = matches!(acceptance, MessageAcceptance::Accept);
+        if !accepted {
+            debug!("🚫 {:?} message {} on {} from {}", acceptance, message_id, message.topic, propagation_source);
+            if message.topic == self.handshake_topic.hash() {
+                self.metrics.handshakes_invalid.inc();
+            }
+        }
+
+        if let Err(e) = self.swarm
+            .behaviour_mut()
+            .gossipsub
+            .report_message_validation_result(message_id, propagation_source, acceptance)
+        {
+            debug!("Failed to report validation result for {}: {}", message_id, e);
+        }
+        accepted
+    }
+
+    /// Answer the command that started Kademlia query `id`. Lookups stay
+    /// pending across progress steps until enough records arrived or `last`
+    /// says the query is over.
+    fn handle_query_result(&mut self, id: kad::QueryId, result: kad::QueryResult, last: bool) {
+        let Some(pending) = self.pending_queries.remove(&id) else {
+            return;
+        };
+        match (pending, result) {
+            (PendingQuery::ClosestPeers(reply), kad::QueryResult::GetClosestPeers(result)) => {
+                let result = result
+                    .map(|ok| ok.peers)
+                    .map_err(|e| anyhow!("Closest peers query failed: {}", e));
+                let _ = reply.send(result);
+            }
+            (PendingQuery::PutRecord(reply), kad::QueryResult::PutRecord(result)) => {
+                let result = match result {
+                    Ok(_) => Ok(PutRecordOutcome::Stored),
+                    Err(kad::PutRecordError::QuorumFailed { success, quorum, .. })
+                    | Err(kad::PutRecordError::Timeout { success, quorum, .. }) => {
+                        Ok(PutRecordOutcome::QuorumFailed { stored_at: success, quorum: quorum.get() })
+                    }
+                };
+                let _ = reply.send(result);
+            }
+            (PendingQuery::PutRecordAs { reply, record, quorum }, kad::QueryResult::GetClosestPeers(result)) => {
+                let peers = match result {
+                    Ok(ok) => ok.peers,
+                    Err(kad::GetClosestPeersError::Timeout { peers, .. }) => peers,
+                };
+                // Same quorum as put_record, which counts against the replication factor
+                let quorum = quorum.required(kad::K_VALUE.get());
+                let Some(kademlia) = self.kademlia() else {
+                    let _ = reply.send(Err(anyhow!("DHT is disabled")));
+                    return;
+                };
+                let kad_quorum = kad::Quorum::N(NonZeroUsize::new(quorum).expect("quorum is at least one"));
+                let id = kademlia.put_record_to(record, peers.clone().into_iter(), kad_quorum);
+                self.pending_queries.insert(id, PendingQuery::PutRecordTo { reply, peers, quorum });
+            }
+            (PendingQuery::PutRecordTo { reply, peers, quorum }, kad::QueryResult::PutRecord(result)) => {
+                let stored_at = match result {
+                    // put_record_to lowers the quorum to the number of peers, so when it
+                    // had to, success means every peer stored the record
+                    Ok(_) => peers,
+                    Err(kad::PutRecordError::QuorumFailed { success, .. })
+                    | Err(kad::PutRecordError::Timeout { success, .. }) => success,
+                };
+                let outcome = if stored_at.len() >= quorum {
+                    PutRecordOutcome::Stored
+                } else {
+                    PutRecordOutcome::QuorumFailed { stored_at, quorum }
+                };
+                let _ = reply.send(Ok(outcome));
+            }
+            (PendingQuery::GetRecord { reply, quorum, mut records }, kad::QueryResult::GetRecord(result)) => {
+                let result = match result {
+                    Ok(kad::GetRecordOk::FoundRecord(peer_record)) => {
+                        records.push(found_record(peer_record));
+                        if records.len() >= quorum {
+                            // Enough answers, stop asking further peers
+                            if let Some(mut query) = self.kademlia().and_then(|k| k.query_mut(&id)) {
+                                query.finish();
+                            }
+                            Ok(GetRecordOutcome::Found { records })
+                        } else if last {
+                            Ok(GetRecordOutcome::QuorumFailed { records, quorum })
+                        } else {
+                            self.pending_queries.insert(id, PendingQuery::GetRecord { reply, quorum, records });
+                            return;
+                        }
+                    }
+                    Ok(kad::GetRecordOk::FinishedWithNoAdditionalRecord { .. })
+                    | Err(kad::GetRecordError::NotFound { .. })
+                    | Err(kad::GetRecordError::Timeout { .. }) if records.is_empty() => {
+                        Ok(GetRecordOutcome::NotFound)
+                    }
+                    Ok(kad::GetRecordOk::FinishedWithNoAdditionalRecord { .. })
+                    | Err(kad::GetRecordError::NotFound { .. })
+                    | Err(kad::GetRecordError::Timeout { .. }) => Ok(GetRecordOutcome::QuorumFailed { records, quorum }),
+                    Err(kad::GetRecordError::QuorumFailed { records: found, .. }) => {
+                        records.extend(found.into_iter().map(found_record));
+                        Ok(GetRecordOutcome::QuorumFailed { records, quorum })
+                    }
+                };
+                let _ = reply.send(result);
+            }
+            (PendingQuery::StartProviding(reply), kad::QueryResult::StartProviding(result)) => {
+                let result = self
+                    .provider_announced(result)
+                    .map_err(|e| anyhow!("Failed to announce provider record: {}", e));
+                let _ = reply.send(result);
+            }
+            (PendingQuery::GetProviders { reply, mut providers }, kad::QueryResult::GetProviders(result)) => {
+                match result {
+                    Ok(kad::GetProvidersOk::FoundProviders { key, providers: found }) => {
+                        let found: Vec<PeerId> = found.into_iter().filter(|p| !providers.contains(p)).collect();
+                        self.providers_found(key, &found);
+                        providers.extend(found);
+                    }
+                    Ok(kad::GetProvidersOk::FinishedWithNoAdditionalRecord { .. }) => {}
+                    // Whatever was found before the timeout is still worth returning
+                    Err(kad::GetProvidersError::Timeout { .. }) => {
+                        debug!("Provider lookup {:?} timed out", id);
+                    }
+                }
+                if last {
+                    let _ = reply.send(Ok(providers));
+                } else {
+                    self.pending_queries.insert(id, PendingQuery::GetProviders { reply, providers });
+                }
+            }
+            (_, result) => {
+                warn!("Unexpected result for Kademlia query {:?}: {:?}", id, result);
             }
         }
     }
 
+    fn emit(&self, event: NodeEvent) {
+        // Fails only when nobody is subscribed
+        if let Some(events) = &self.events {
+            let _ = events.send(event);
+        }
+    }
+
+    /// Refuse further commands, fail the queued ones and end every event stream.
+    fn close_channels(&mut self) {
+        self.command_tx = None;
+        self.command_rx.close();
+        // Dropping a reply sender fails the waiting call with NodeStopped
+        while self.command_rx.try_recv().is_ok() {}
+        self.events = None;
+    }
+
+    /// Kademlia behaviour, if the DHT is enabled.
+    fn kademlia(&mut self) -> Option<&mut kad::Behaviour<DhtStore>> {
+        self.swarm.behaviour_mut().kademlia.as_mut()
+    }
+
+    /// Announce the configured keys now that the DHT has somewhere to send them.
+    fn announce_provided_keys(&mut self) {
+        for key in std::mem::take(&mut self.unannounced_keys) {
+            let Some(kademlia) = self.kademlia() else {
+                return;
+            };
+            if let Err(e) = kademlia.start_providing(key.clone()) {
+                warn!("⚠️ Failed to provide {}: {:?}", String::from_utf8_lossy(key.as_ref()), e);
+            }
+        }
+    }
+
+    /// Log and report the outcome of announcing one of our provider records.
+    fn provider_announced(&mut self, result: kad::AddProviderResult) -> Result<(), kad::AddProviderError> {
+        match &result {
+            Ok(kad::AddProviderOk { key }) => {
+                info!("📦 Announced as provider of {}", String::from_utf8_lossy(key.as_ref()));
+                self.emit(NodeEvent::ProviderAnnounced { key: key.to_vec() });
+            }
+            Err(e) => warn!("⚠️ Failed to announce provider of {}: {}", String::from_utf8_lossy(e.key().as_ref()), e),
+        }
+        result.map(|_| ())
+    }
+
+    /// Report newly found providers of `key` and dial those we are not connected to.
+    fn providers_found(&mut self, key: kad::RecordKey, providers: &[PeerId]) {
+        if providers.is_empty() {
+            return;
+        }
+        info!("📦 Found {} providers of {}", providers.len(), String::from_utf8_lossy(key.as_ref()));
+        if self.dial_providers {
+            let local_peer_id = *self.swarm.local_peer_id();
+            for &peer_id in providers.iter().filter(|&&p| p != local_peer_id) {
+                let opts = DialOpts::peer_id(peer_id)
+                    .condition(PeerCondition::DisconnectedAndNotDialing)
+                    .build();
+                match self.swarm.dial(opts) {
+                    Ok(()) | Err(DialError::DialPeerConditionFalse(_)) => {}
+                    Err(e) => debug!("Failed to dial provider {}: {}", peer_id, e),
+                }
+            }
+        }
+        self.emit(NodeEvent::ProvidersFound { key: key.to_vec(), providers: providers.to_vec() });
+    }
+
+    /// Send our signed handshake over a connection we dialed; the response
+    /// carries the peer's handshake.
+    fn start_handshake(&mut self, peer_id: PeerId) {
+        match self.signed_handshake() {
+            Ok(request) => {
+                self.swarm.behaviour_mut().handshake.send_request(&peer_id, request);
+            }
+            Err(e) => error!("{}", e),
+        }
+    }
+
+    fn signed_handshake(&self) -> Result<SignedHandshake> {
+        let info = HandshakeInfo {
+            protocol_version: self.protocol_version.clone(),
+            node_name: self.node_name.clone(),
+            agent_version: AGENT_VERSION.to_string(),
+            capabilities: self.capabilities.clone(),
+            topics: self.swarm.behaviour().gossipsub.topics().map(|hash| hash.to_string()).collect(),
+            timestamp: unix_time(),
+        };
+        SignedHandshake::sign(&info, &self.local_key)
+    }
+
+    fn handle_handshake_event(&mut self, event: request_response::Event<SignedHandshake, SignedHandshake>) {
+        match event {
+            request_response::Event::Message { peer, message } => match message {
+                request_response::Message::Request { request, channel, .. } => {
+                    if !self.accept_handshake(peer, &request) {
+                        return;
+                    }
+                    match self.signed_handshake() {
+                        Ok(response) => {
+                            if self.swarm.behaviour_mut().handshake.send_response(channel, response).is_err() {
+                                debug!("Connection to {} closed before our handshake was sent", peer);
+                            }
+                        }
+                        Err(e) => error!("{}", e),
+                    }
+                }
+                request_response::Message::Response { response, .. } => {
+                    self.accept_handshake(peer, &response);
+                }
+            },
+            request_response::Event::OutboundFailure { peer, error: request_response::OutboundFailure::UnsupportedProtocols, .. } => {
+                debug!("{} does not support {}", peer, HANDSHAKE_PROTOCOL);
+            }
+            request_response::Event::OutboundFailure { peer, error, .. } => {
+                warn!("❌ Handshake with {} failed: {}", peer, error);
+            }
+            request_response::Event::InboundFailure { peer, error, .. } => {
+                debug!("Inbound handshake from {} failed: {}", peer, error);
+            }
+            request_response::Event::ResponseSent { .. } => {}
+        }
+    }
+
+    /// Verify and record a peer's handshake. Peers whose handshake is forged,
+    /// stale or for an incompatible protocol version are disconnected.
+    fn accept_handshake(&mut self, peer_id: PeerId, handshake: &SignedHandshake) -> bool {
+        let result = handshake.verify(&peer_id).and_then(|info| {
+            if !handshake::compatible(&self.protocol_version, &info.protocol_version) {
+                return Err(anyhow!(
+                    "incompatible protocol version {} (we speak {})",
+                    info.protocol_version,
+                    self.protocol_version
+                ));
+            }
+            let skew = unix_time().abs_diff(info.timestamp);
+            if skew > self.handshake_max_clock_skew.as_secs() {
+                return Err(anyhow!("timestamp is {}s from our clock", skew));
+            }
+            Ok(info)
+        });
+
+        match result {
+            Ok(info) => {
+                info!(
+                    "🤝 Handshake with {} ({}, {}), shared capabilities: [{}]",
+                    info.node_name.as_deref().unwrap_or("unnamed"),
+                    peer_id,
+                    info.agent_version,
+                    info.shared_capabilities(&self.capabilities).join(", ")
+                );
+                if let Some(store) = &mut self.peer_store {
+                    store.named(peer_id, info.node_name.clone());
+                }
+                self.peer_handshakes.insert(peer_id, info.clone());
+                self.emit(NodeEvent::HandshakeCompleted { peer_id, info });
+                true
+            }
+            Err(e) => {
+                warn!("🚫 Disconnecting {}: {}", peer_id, e);
+                let _ = self.swarm.disconnect_peer_id(peer_id);
+                false
+            }
+        }
+    }
+
+    /// Periodic presence beacon on the handshake topic. New peers learn
+    /// about us through the signed handshake protocol instead.
     async fn broadcast_handshake(&mut self) {
         let connected_peers: Vec<PeerId> = self.swarm.connected_peers().cloned().collect();
         
@@ -448,15 +1541,13 @@ impl P2PNode {
             let handshake = HandshakeMessage {
                 node_name: self.node_name.clone(),
                 peer_id: self.swarm.local_peer_id().to_string(),
-                timestamp: std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs(),
+                timestamp: unix_time(),
                 message: format!(
                     "Periodic handshake from {}! Current time: {}",
                     self.node_name.as_deref().unwrap_or("Anonymous Node"),
                     chrono::Utc::now().format("%H:%M:%S")
                 ),
+                leaving: false,
             };
 
             if let Ok(message_json) = serde_json::to_string(&handshake) {
@@ -466,24 +1557,147 @@ impl P2PNode {
                     .publish(self.handshake_topic.clone(), message_json.as_bytes())
                 {
                     error!("Failed to broadcast handshake: {}", e);
+                } else {
+                    self.metrics.handshakes_sent.inc();
                 }
             }
         }
     }
 
-    async fn handle_handshake_message(&self, peer_id: PeerId, data: &[u8]) {
-        match serde_json::from_slice::<HandshakeMessage>(data) {
+    /// Handle a validated handshake. It is attributed to the peer that signed
+    /// it, which for relayed handshakes is not the peer that forwarded it.
+    async fn handle_handshake_message(&mut self, message: &gossipsub::Message) {
+        // The handshake validator rejects unsigned handshakes, a custom one may not
+        let Some(peer_id) = message.source else {
+            warn!("Ignoring unsigned handshake message");
+            self.metrics.handshakes_invalid.inc();
+            return;
+        };
+        match serde_json::from_slice::<HandshakeMessage>(&message.data) {
             Ok(handshake) => {
-                info!(
-                    "🤝 Received handshake from {} ({}): {}",
-                    peer_id,
-                    handshake.node_name.as_deref().unwrap_or("Anonymous"),
-                    handshake.message
-                );
+                if handshake.leaving {
+                    info!(
+                        "👋 {} ({}) is leaving: {}",
+                        handshake.peer_id,
+                        handshake.node_name.as_deref().unwrap_or("Anonymous"),
+                        handshake.message
+                    );
+                } else {
+                    info!(
+                        "🤝 Received handshake from {} ({}): {}",
+                        peer_id,
+                        handshake.node_name.as_deref().unwrap_or("Anonymous"),
+                        handshake.message
+                    );
+                }
+                self.metrics.handshakes_received.inc();
+                self.emit(NodeEvent::HandshakeReceived { peer_id, handshake });
             }
             Err(e) => {
                 warn!("Failed to parse handshake message: {}", e);
+                self.metrics.handshakes_invalid.inc();
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Whether a transport error happened while negotiating the security
+/// protocol. Behind a swarm key this is what a key mismatch looks like, since
+/// pnet itself cannot detect one: the peer's bytes are noise to us.
+fn is_handshake_failure(error: &TransportError<std::io::Error>) -> bool {
+    let TransportError::Other(e) = error else {
+        return false;
+    };
+    // io::Error::source skips the wrapped error itself, so start from it
+    let mut cause = e.get_ref().map(|inner| inner as &(dyn std::error::Error + 'static));
+    while let Some(e) = cause {
+        if e.is::<NegotiationError>() || e.is::<noise::Error>() {
+            return true;
+        }
+        cause = e.source();
+    }
+    false
+}
+
+/// SHA-256 message ID, identical on every node regardless of platform or toolchain.
+///
+/// Content-addressed IDs still include the topic, so the same payload on two
+/// topics is not mistaken for a duplicate.
+fn message_id(message: &gossipsub::Message, mode: MessageIdMode) -> MessageId {
+    let mut hasher = Sha256::new();
+    match mode {
+        MessageIdMode::Publisher => {
+            let source = message.source.map(|peer_id| peer_id.to_bytes()).unwrap_or_default();
+            hasher.update((source.len() as u64).to_be_bytes());
+            hasher.update(&source);
+            hasher.update(message.sequence_number.unwrap_or_default().to_be_bytes());
+        }
+        MessageIdMode::Content => {
+            let topic = message.topic.as_str().as_bytes();
+            hasher.update((topic.len() as u64).to_be_bytes());
+            hasher.update(topic);
+        }
+    }
+    hasher.update(&message.data);
+    MessageId::new(&hasher.finalize())
+}
+
+fn found_record(peer_record: kad::PeerRecord) -> FoundRecord {
+    let kad::PeerRecord { peer, record } = peer_record;
+    FoundRecord {
+        value: record.value,
+        publisher: record.publisher,
+        peer,
+        expires_in_secs: record
+            .expires
+            .map(|at| at.saturating_duration_since(std::time::Instant::now()).as_secs()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(source: PeerId, sequence_number: u64, topic: &str, data: &[u8]) -> gossipsub::Message {
+        gossipsub::Message {
+            source: Some(source),
+            data: data.to_vec(),
+            sequence_number: Some(sequence_number),
+            topic: IdentTopic::new(topic).hash(),
+        }
+    }
+
+    #[test]
+    fn content_ids_ignore_the_publisher() {
+        let a = message(PeerId::random(), 1, "datasets", b"same payload");
+        let b = message(PeerId::random(), 7, "datasets", b"same payload");
+        assert_eq!(message_id(&a, MessageIdMode::Content), message_id(&b, MessageIdMode::Content));
+
+        let other_topic = message(PeerId::random(), 1, "other", b"same payload");
+        assert_ne!(message_id(&a, MessageIdMode::Content), message_id(&other_topic, MessageIdMode::Content));
+    }
+
+    #[test]
+    fn publisher_ids_tell_publishes_apart() {
+        let publisher = PeerId::random();
+        let a = message(publisher, 1, "datasets", b"same payload");
+        let b = message(PeerId::random(), 1, "datasets", b"same payload");
+        let again = message(publisher, 2, "datasets", b"same payload");
+        assert_ne!(message_id(&a, MessageIdMode::Publisher), message_id(&b, MessageIdMode::Publisher));
+        assert_ne!(message_id(&a, MessageIdMode::Publisher), message_id(&again, MessageIdMode::Publisher));
+        assert_eq!(message_id(&a, MessageIdMode::Publisher), message_id(&a.clone(), MessageIdMode::Publisher));
+    }
+
+    #[test]
+    fn only_failed_negotiation_looks_like_a_swarm_key_mismatch() {
+        use std::io::{Error, ErrorKind};
+
+        let negotiation = Error::other(NegotiationError::Failed);
+        assert!(is_handshake_failure(&TransportError::Other(negotiation)));
+
+        let dns = Error::other("failed to resolve does-not-exist.invalid");
+        assert!(!is_handshake_failure(&TransportError::Other(dns)));
+        let refused = Error::from(ErrorKind::ConnectionRefused);
+        assert!(!is_handshake_failure(&TransportError::Other(refused)));
+    }
+}
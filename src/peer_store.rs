@@ -0,0 +1,223 @@
+use anyhow::Result;
+use libp2p::{multiaddr::Protocol, Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, path::{Path, PathBuf}, time::Duration};
+use tracing::{info, warn};
+
+use crate::util::{set_aside, write_atomically};
+
+/// Addresses remembered per peer; older ones are dropped first.
+const MAX_ADDRS_PER_PEER: usize = 8;
+
+/// Peers kept in the store; the least recently seen are dropped first.
+const MAX_PEERS: usize = 1000;
+
+/// Peers redialed on startup.
+const MAX_REDIAL: usize = 20;
+
+/// Consecutive failed dials after which a peer is no longer redialed on startup.
+const MAX_FAILURES: u32 = 5;
+
+/// What we know about a peer from previous sessions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KnownPeer {
+    /// Dialable addresses: the ones we connected through, most recently
+    /// confirmed first, then the ones the peer reported itself.
+    pub addresses: Vec<Multiaddr>,
+    pub agent_version: Option<String>,
+    pub protocol_version: Option<String>,
+    /// Name from the peer's last handshake.
+    pub node_name: Option<String>,
+    /// Unix time in seconds when we were last connected.
+    pub last_seen: Option<u64>,
+    /// Last ping round-trip time in milliseconds.
+    pub rtt_ms: Option<u64>,
+    /// Failed dials since the last successful connection.
+    pub failures: u32,
+}
+
+/// Peers seen in previous sessions, kept as a JSON file.
+///
+/// Changes are collected in memory and written by [`PeerStore::flush`], which
+/// the node calls periodically and on shutdown.
+#[derive(Debug)]
+pub(crate) struct PeerStore {
+    path: PathBuf,
+    peers: HashMap<PeerId, KnownPeer>,
+    dirty: bool,
+}
+
+impl PeerStore {
+    /// Open the store at `path`. A missing file is an empty store; a corrupt
+    /// one is set aside rather than blocking startup.
+    pub(crate) fn load(path: &Path) -> Self {
+        let peers = match fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                set_aside(path, "peer store", e);
+                HashMap::new()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                warn!("⚠️ Failed to read peer store {}: {}", path.display(), e);
+                HashMap::new()
+            }
+        };
+        info!("📒 Loaded {} known peers from {}", peers.len(), path.display());
+        Self { path: path.to_path_buf(), peers, dirty: false }
+    }
+
+    /// Write the store if anything changed since the last flush.
+    pub(crate) fn flush(&mut self) -> Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        self.prune();
+
+        write_atomically(&self.path, &serde_json::to_vec_pretty(&self.peers)?, "peer store")?;
+
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Peers worth redialing, most recently seen first.
+    pub(crate) fn redial_candidates(&self) -> Vec<(PeerId, Vec<Multiaddr>)> {
+        let mut candidates: Vec<(&PeerId, &KnownPeer)> = self
+            .peers
+            .iter()
+            .filter(|(_, peer)| !peer.addresses.is_empty() && peer.failures < MAX_FAILURES)
+            .collect();
+        candidates.sort_by_key(|(_, peer)| std::cmp::Reverse(peer.last_seen));
+        candidates
+            .into_iter()
+            .take(MAX_REDIAL)
+            .map(|(peer_id, peer)| (*peer_id, peer.addresses.clone()))
+            .collect()
+    }
+
+    /// A connection was established, through `address` if we dialed it.
+    pub(crate) fn connected(&mut self, peer_id: PeerId, address: Option<&Multiaddr>, now: u64) {
+        let peer = self.entry(peer_id);
+        peer.last_seen = Some(now);
+        peer.failures = 0;
+        if let Some(address) = address {
+            remember_address(peer, address.clone());
+        }
+    }
+
+    pub(crate) fn identified(
+        &mut self,
+        peer_id: PeerId,
+        agent_version: &str,
+        protocol_version: &str,
+        listen_addrs: &[Multiaddr],
+    ) {
+        let peer = self.entry(peer_id);
+        peer.agent_version = Some(agent_version.to_string());
+        peer.protocol_version = Some(protocol_version.to_string());
+        // Self-reported addresses are unconfirmed, so they never push out one we dialed
+        for address in listen_addrs.iter().filter(|a| !is_loopback(a)) {
+            if peer.addresses.len() < MAX_ADDRS_PER_PEER && !peer.addresses.contains(address) {
+                peer.addresses.push(address.clone());
+            }
+        }
+    }
+
+    pub(crate) fn named(&mut self, peer_id: PeerId, node_name: Option<String>) {
+        self.entry(peer_id).node_name = node_name;
+    }
+
+    pub(crate) fn pinged(&mut self, peer_id: PeerId, rtt: Duration) {
+        self.entry(peer_id).rtt_ms = Some(rtt.as_millis() as u64);
+    }
+
+    /// A dial failed. Only peers we already know are tracked, so failed
+    /// dials to random addresses do not fill the store.
+    pub(crate) fn dial_failed(&mut self, peer_id: &PeerId) {
+        if let Some(peer) = self.peers.get_mut(peer_id) {
+            peer.failures += 1;
+            self.dirty = true;
+        }
+    }
+
+    fn entry(&mut self, peer_id: PeerId) -> &mut KnownPeer {
+        self.dirty = true;
+        self.peers.entry(peer_id).or_default()
+    }
+
+    fn prune(&mut self) {
+        if self.peers.len() <= MAX_PEERS {
+            return;
+        }
+        let mut by_age: Vec<(PeerId, Option<u64>)> =
+            self.peers.iter().map(|(peer_id, peer)| (*peer_id, peer.last_seen)).collect();
+        by_age.sort_by_key(|(_, last_seen)| *last_seen);
+        for (peer_id, _) in by_age.into_iter().take(self.peers.len() - MAX_PEERS) {
+            self.peers.remove(&peer_id);
+        }
+    }
+}
+
+fn remember_address(peer: &mut KnownPeer, address: Multiaddr) {
+    peer.addresses.retain(|a| *a != address);
+    peer.addresses.insert(0, address);
+    peer.addresses.truncate(MAX_ADDRS_PER_PEER);
+}
+
+/// Loopback addresses reported by another peer point back at ourselves.
+fn is_loopback(address: &Multiaddr) -> bool {
+    match address.iter().next() {
+        Some(Protocol::Ip4(ip)) => ip.is_loopback(),
+        Some(Protocol::Ip6(ip)) => ip.is_loopback(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corrupt_store_is_set_aside() {
+        let dir = std::env::temp_dir().join(format!("node_eeb-peer-store-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("peers.json");
+        fs::write(&path, b"{ not json").unwrap();
+
+        let mut store = PeerStore::load(&path);
+        assert!(store.redial_candidates().is_empty());
+        assert_eq!(fs::read(dir.join("peers.json.corrupt")).unwrap(), b"{ not json");
+
+        store.connected(PeerId::random(), Some(&"/ip4/127.0.0.1/tcp/1".parse().unwrap()), 1);
+        store.flush().unwrap();
+        assert_eq!(PeerStore::load(&path).redial_candidates().len(), 1);
+        assert_eq!(fs::read(dir.join("peers.json.corrupt")).unwrap(), b"{ not json");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dialed_address_outranks_reported_ones() {
+        let mut store = PeerStore { path: PathBuf::new(), peers: HashMap::new(), dirty: false };
+        let peer_id = PeerId::random();
+        let dialed: Multiaddr = "/ip4/203.0.113.7/tcp/4001".parse().unwrap();
+        let reported: Vec<Multiaddr> = (0..MAX_ADDRS_PER_PEER)
+            .map(|i| format!("/ip4/172.17.0.{}/tcp/4001", i + 2).parse().unwrap())
+            .chain(["/ip4/127.0.0.1/tcp/4001".parse().unwrap()])
+            .collect();
+
+        store.connected(peer_id, Some(&dialed), 1);
+        store.identified(peer_id, "agent", "protocol", &reported);
+
+        let addresses = &store.peers[&peer_id].addresses;
+        assert_eq!(addresses[0], dialed);
+        assert_eq!(addresses.len(), MAX_ADDRS_PER_PEER);
+        assert!(!addresses.iter().any(is_loopback));
+
+        // A newly confirmed address goes first and drops a reported one
+        let redialed: Multiaddr = "/ip4/198.51.100.1/tcp/4001".parse().unwrap();
+        store.connected(peer_id, Some(&redialed), 2);
+        let addresses = &store.peers[&peer_id].addresses;
+        assert_eq!(addresses[..2], [redialed, dialed]);
+        assert_eq!(addresses.len(), MAX_ADDRS_PER_PEER);
+    }
+}
@@ -0,0 +1,312 @@
+use anyhow::{Context, Result};
+use libp2p::{
+    kad::{
+        store::{self, MemoryStore, MemoryStoreConfig, RecordStore},
+        ProviderRecord, Record, RecordKey,
+    },
+    Multiaddr, PeerId,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+use crate::util::{set_aside, unix_time, write_atomically};
+
+/// Kademlia record store kept in memory and, when given a path, saved to a
+/// JSON file so records and provider entries survive restarts.
+///
+/// Limits are enforced by the wrapped [`MemoryStore`]. Changes are written by
+/// [`DhtStore::flush`], which the node calls periodically and on shutdown.
+pub struct DhtStore {
+    inner: MemoryStore,
+    path: Option<PathBuf>,
+    /// Keys with provider records, which `MemoryStore` cannot enumerate.
+    provider_keys: HashSet<RecordKey>,
+    dirty: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoreFile {
+    records: Vec<StoredRecord>,
+    providers: Vec<StoredProvider>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredRecord {
+    key: Vec<u8>,
+    value: Vec<u8>,
+    publisher: Option<PeerId>,
+    /// Unix time in seconds.
+    expires: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredProvider {
+    key: Vec<u8>,
+    provider: PeerId,
+    addresses: Vec<Multiaddr>,
+    /// Unix time in seconds.
+    expires: Option<u64>,
+}
+
+impl DhtStore {
+    /// A store that lives in memory only.
+    pub(crate) fn memory(local_peer_id: PeerId, config: MemoryStoreConfig) -> Self {
+        Self {
+            inner: MemoryStore::with_config(local_peer_id, config),
+            path: None,
+            provider_keys: HashSet::new(),
+            dirty: false,
+        }
+    }
+
+    /// Open the store saved at `path`, dropping entries that expired while
+    /// the node was down. A missing file is an empty store; a corrupt one is
+    /// set aside like a corrupt peer store.
+    pub(crate) fn open(local_peer_id: PeerId, config: MemoryStoreConfig, path: &Path) -> Result<Self> {
+        let mut store = Self::memory(local_peer_id, config);
+        store.path = Some(path.to_path_buf());
+
+        let file: StoreFile = match fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                set_aside(path, "DHT record store", e);
+                StoreFile::default()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => StoreFile::default(),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read DHT record store {}", path.display()))
+            }
+        };
+
+        let now = unix_time();
+        let (mut records, mut providers) = (0, 0);
+        for stored in file.records {
+            if stored.expires.is_some_and(|at| at <= now) {
+                continue;
+            }
+            let record = Record {
+                key: RecordKey::from(stored.key),
+                value: stored.value,
+                publisher: stored.publisher,
+                expires: stored.expires.map(to_instant),
+            };
+            match store.inner.put(record) {
+                Ok(()) => records += 1,
+                Err(e) => warn!("⚠️ Dropping stored DHT record: {:?}", e),
+            }
+        }
+        for stored in file.providers {
+            if stored.expires.is_some_and(|at| at <= now) {
+                continue;
+            }
+            let record = ProviderRecord {
+                key: RecordKey::from(stored.key),
+                provider: stored.provider,
+                expires: stored.expires.map(to_instant),
+                addresses: stored.addresses,
+            };
+            store.provider_keys.insert(record.key.clone());
+            match store.inner.add_provider(record) {
+                Ok(()) => providers += 1,
+                Err(e) => warn!("⚠️ Dropping stored provider record: {:?}", e),
+            }
+        }
+
+        info!("🗄️ Loaded {} DHT records and {} provider records from {}", records, providers, path.display());
+        Ok(store)
+    }
+
+    /// Save the store if it is backed by a file and changed since the last flush.
+    pub(crate) fn flush(&mut self) -> Result<()> {
+        let Some(path) = self.path.as_ref().filter(|_| self.dirty) else {
+            return Ok(());
+        };
+
+        let now = Instant::now();
+        let file = StoreFile {
+            records: self
+                .inner
+                .records()
+                .filter(|record| !record.is_expired(now))
+                .map(|record| StoredRecord {
+                    key: record.key.to_vec(),
+                    value: record.value.clone(),
+                    publisher: record.publisher,
+                    expires: record.expires.map(to_unix_time),
+                })
+                .collect(),
+            providers: self
+                .provider_keys
+                .iter()
+                .flat_map(|key| self.inner.providers(key))
+                .filter(|record| !record.is_expired(now))
+                .map(|record| StoredProvider {
+                    key: record.key.to_vec(),
+                    provider: record.provider,
+                    addresses: record.addresses,
+                    expires: record.expires.map(to_unix_time),
+                })
+                .collect(),
+        };
+
+        write_atomically(path, &serde_json::to_vec(&file)?, "DHT record store")?;
+
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+impl RecordStore for DhtStore {
+    type RecordsIter<'a> = <MemoryStore as RecordStore>::RecordsIter<'a>;
+    type ProvidedIter<'a> = <MemoryStore as RecordStore>::ProvidedIter<'a>;
+
+    fn get(&self, key: &RecordKey) -> Option<Cow<'_, Record>> {
+        self.inner.get(key)
+    }
+
+    fn put(&mut self, record: Record) -> store::Result<()> {
+        self.inner.put(record)?;
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &RecordKey) {
+        self.inner.remove(key);
+        self.dirty = true;
+    }
+
+    fn records(&self) -> Self::RecordsIter<'_> {
+        self.inner.records()
+    }
+
+    fn add_provider(&mut self, record: ProviderRecord) -> store::Result<()> {
+        let key = record.key.clone();
+        self.inner.add_provider(record)?;
+        self.provider_keys.insert(key);
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn providers(&self, key: &RecordKey) -> Vec<ProviderRecord> {
+        self.inner.providers(key)
+    }
+
+    fn provided(&self) -> Self::ProvidedIter<'_> {
+        self.inner.provided()
+    }
+
+    fn remove_provider(&mut self, key: &RecordKey, provider: &PeerId) {
+        self.inner.remove_provider(key, provider);
+        if self.inner.providers(key).is_empty() {
+            self.provider_keys.remove(key);
+        }
+        self.dirty = true;
+    }
+}
+
+fn to_unix_time(at: Instant) -> u64 {
+    unix_time() + at.saturating_duration_since(Instant::now()).as_secs()
+}
+
+fn to_instant(at: u64) -> Instant {
+    Instant::now() + Duration::from_secs(at.saturating_sub(unix_time()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory for one test's store file.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("node_eeb-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn records_and_providers_survive_a_restart() {
+        let dir = test_dir("record-store-round-trip");
+        let path = dir.join("dht.json");
+        let (local, publisher, provider) = (PeerId::random(), PeerId::random(), PeerId::random());
+        let address: Multiaddr = "/ip4/203.0.113.7/tcp/4001".parse().unwrap();
+
+        let mut store = DhtStore::open(local, MemoryStoreConfig::default(), &path).unwrap();
+        let mut record = Record::new(RecordKey::new(&"dataset"), b"v1".to_vec());
+        record.publisher = Some(publisher);
+        record.expires = Some(Instant::now() + Duration::from_secs(3600));
+        store.put(record).unwrap();
+        store.put(Record::new(RecordKey::new(&"forever"), b"v2".to_vec())).unwrap();
+        let mut provided = ProviderRecord::new(RecordKey::new(&"dataset"), provider, vec![address.clone()]);
+        provided.expires = Some(Instant::now() + Duration::from_secs(3600));
+        store.add_provider(provided).unwrap();
+        store.flush().unwrap();
+
+        let store = DhtStore::open(local, MemoryStoreConfig::default(), &path).unwrap();
+        let record = store.get(&RecordKey::new(&"dataset")).unwrap();
+        assert_eq!(record.value, b"v1");
+        assert_eq!(record.publisher, Some(publisher));
+        let left = record.expires.unwrap().saturating_duration_since(Instant::now());
+        assert!(left > Duration::from_secs(3500) && left <= Duration::from_secs(3600), "{:?}", left);
+        assert_eq!(store.get(&RecordKey::new(&"forever")).unwrap().expires, None);
+
+        let providers = store.providers(&RecordKey::new(&"dataset"));
+        assert_eq!(providers.len(), 1);
+        assert_eq!(providers[0].provider, provider);
+        assert_eq!(providers[0].addresses, vec![address]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn expired_entries_are_dropped_on_load() {
+        let dir = test_dir("record-store-expiry");
+        let path = dir.join("dht.json");
+        let record = |key: &[u8], expires| StoredRecord {
+            key: key.to_vec(),
+            value: b"v".to_vec(),
+            publisher: None,
+            expires: Some(expires),
+        };
+        let provider = |key: &[u8], expires| StoredProvider {
+            key: key.to_vec(),
+            provider: PeerId::random(),
+            addresses: Vec::new(),
+            expires: Some(expires),
+        };
+        let (past, future) = (unix_time() - 10, unix_time() + 3600);
+        let file = StoreFile {
+            records: vec![record(b"stale", past), record(b"fresh", future)],
+            providers: vec![provider(b"stale", past), provider(b"fresh", future)],
+        };
+        fs::write(&path, serde_json::to_vec(&file).unwrap()).unwrap();
+
+        let store = DhtStore::open(PeerId::random(), MemoryStoreConfig::default(), &path).unwrap();
+        assert!(store.get(&RecordKey::from(b"stale".to_vec())).is_none());
+        assert!(store.get(&RecordKey::from(b"fresh".to_vec())).is_some());
+        assert!(store.providers(&RecordKey::from(b"stale".to_vec())).is_empty());
+        assert_eq!(store.providers(&RecordKey::from(b"fresh".to_vec())).len(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn corrupt_store_is_set_aside() {
+        let dir = test_dir("record-store-corrupt");
+        let path = dir.join("dht.json");
+        fs::write(&path, b"{ not json").unwrap();
+
+        let store = DhtStore::open(PeerId::random(), MemoryStoreConfig::default(), &path).unwrap();
+        assert_eq!(store.records().count(), 0);
+        assert!(!path.exists());
+        assert_eq!(fs::read(dir.join("dht.json.corrupt")).unwrap(), b"{ not json");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
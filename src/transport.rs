@@ -0,0 +1,83 @@
+use anyhow::{anyhow, Context, Result};
+use futures::future::Either;
+use libp2p::{
+    core::{
+        muxing::StreamMuxerBox,
+        transport::{Boxed, OptionalTransport, OrTransport},
+        upgrade::Version,
+    },
+    identity::Keypair,
+    dns,
+    metrics::{BandwidthTransport, Registry},
+    noise,
+    pnet::{PnetConfig, PreSharedKey},
+    quic, relay, tcp, websocket, yamux, PeerId, Transport,
+};
+use std::{fs, path::Path};
+use tracing::info;
+
+/// Build the node transport: QUIC, plus TCP, WebSocket and relayed circuits
+/// secured with noise and multiplexed with yamux. `/dns*` addresses are
+/// resolved using the system resolver configuration.
+///
+/// With a pre-shared key every TCP, WebSocket and relayed connection is
+/// wrapped in libp2p's private network handshake before noise, and QUIC is
+/// disabled because its built-in TLS cannot be combined with pnet.
+///
+/// Bytes sent and received are counted per protocol stack in `registry`.
+pub(crate) fn build(
+    local_key: &Keypair,
+    relay_transport: relay::client::Transport,
+    psk: Option<PreSharedKey>,
+    registry: &mut Registry,
+) -> Result<Boxed<(PeerId, StreamMuxerBox)>> {
+    // WebSocket runs over its own TCP transport so `/ws` addresses are routed to it
+    let ws_transport = websocket::WsConfig::new(tcp::tokio::Transport::default());
+
+    // TCP, WebSocket and relay circuits share the pnet/noise/yamux upgrade
+    let tcp_transport = OrTransport::new(ws_transport, tcp::tokio::Transport::default());
+    let tcp_transport = OrTransport::new(relay_transport, tcp_transport)
+        .and_then(move |socket, _| async move {
+            match psk {
+                Some(psk) => PnetConfig::new(psk).handshake(socket).await.map(Either::Left),
+                None => Ok(Either::Right(socket)),
+            }
+        })
+        .upgrade(Version::V1)
+        .authenticate(noise::Config::new(local_key)
+            .map_err(|e| anyhow!("Failed to create noise config: {}", e))?)
+        .multiplex(yamux::Config::default());
+
+    // QUIC brings its own encryption and multiplexing
+    let quic_transport = if psk.is_some() {
+        info!("🔒 QUIC disabled in private network mode");
+        OptionalTransport::none()
+    } else {
+        OptionalTransport::some(quic::tokio::Transport::new(quic::Config::new(local_key)))
+    };
+
+    let transport = OrTransport::new(quic_transport, tcp_transport)
+        .map(|output, _| match output {
+            Either::Left((peer_id, muxer)) => (peer_id, StreamMuxerBox::new(muxer)),
+            Either::Right((peer_id, muxer)) => (peer_id, StreamMuxerBox::new(muxer)),
+        });
+
+    let transport = dns::tokio::Transport::system(transport)
+        .map_err(|e| anyhow!("Failed to create DNS transport: {}", e))?;
+
+    Ok(BandwidthTransport::new(transport, registry)
+        .map(|(peer_id, muxer), _| (peer_id, StreamMuxerBox::new(muxer)))
+        .boxed())
+}
+
+/// Read a swarm key in the standard `/key/swarm/psk/1.0.0/` format.
+pub(crate) fn load_swarm_key(path: &Path) -> Result<PreSharedKey> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read swarm key {}", path.display()))?;
+    let psk = contents
+        .parse::<PreSharedKey>()
+        .map_err(|e| anyhow!("Invalid swarm key {}: {}", path.display(), e))?;
+
+    info!("🔒 Private network enabled, swarm key fingerprint: {}", psk.fingerprint());
+    Ok(psk)
+}
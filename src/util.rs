@@ -0,0 +1,55 @@
+//! Helpers shared by the node's modules: the clock and the JSON store files.
+
+use anyhow::{Context, Result};
+use std::{
+    ffi::OsString,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::warn;
+
+/// Seconds since the Unix epoch, as used in handshakes and store files.
+pub(crate) fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Replace the file at `path` with `contents`, creating its directory if
+/// needed. `what` names the file in errors.
+pub(crate) fn write_atomically(path: &Path, contents: &[u8], what: &str) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create directory {}", parent.display()))?;
+    }
+    // Write to a temporary file first so a crash never leaves a truncated store
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, contents).with_context(|| format!("Failed to write {} {}", what, tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("Failed to replace {} {}", what, path.display()))
+}
+
+/// Rename the corrupt store at `path` to `<path>.corrupt` so the next flush
+/// does not overwrite it, leaving it for inspection.
+pub(crate) fn set_aside(path: &Path, what: &str, error: impl Display) {
+    let mut corrupt = OsString::from(path.as_os_str());
+    corrupt.push(".corrupt");
+    let corrupt = PathBuf::from(corrupt);
+    match fs::rename(path, &corrupt) {
+        Ok(()) => warn!(
+            "⚠️ Corrupt {} {} ({}), moved to {} and starting empty",
+            what,
+            path.display(),
+            error,
+            corrupt.display()
+        ),
+        Err(e) => warn!(
+            "⚠️ Corrupt {} {} ({}) could not be moved aside: {}",
+            what,
+            path.display(),
+            error,
+            e
+        ),
+    }
+}
@@ -0,0 +1,147 @@
+use libp2p::gossipsub::{Message, MessageAcceptance};
+use std::{fmt, time::Duration};
+use tracing::warn;
+
+use crate::{p2p_node::HandshakeMessage, util::unix_time};
+
+/// Decides whether a gossipsub message on a topic is delivered and forwarded.
+///
+/// Validators run inside the swarm loop for every message on their topic, so
+/// they should be cheap. [`MessageAcceptance::Reject`] also penalises the
+/// peer that forwarded the message; [`MessageAcceptance::Ignore`] only drops it.
+/// Register one with [`NodeConfigBuilder::validator`](crate::NodeConfigBuilder::validator).
+pub trait MessageValidator: Send + Sync {
+    fn validate(&self, message: &Message) -> MessageAcceptance;
+}
+
+impl<F> MessageValidator for F
+where
+    F: Fn(&Message) -> MessageAcceptance + Send + Sync,
+{
+    fn validate(&self, message: &Message) -> MessageAcceptance {
+        self(message)
+    }
+}
+
+impl fmt::Debug for dyn MessageValidator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MessageValidator")
+    }
+}
+
+/// Built-in validator for the handshake topic.
+///
+/// Rejects payloads that are not a [`HandshakeMessage`], whose `peer_id` is
+/// not the peer that signed the message, or whose timestamp is further than
+/// `max_clock_skew` from our clock.
+#[derive(Debug, Clone)]
+pub struct HandshakeValidator {
+    max_clock_skew: Duration,
+}
+
+impl HandshakeValidator {
+    pub fn new(max_clock_skew: Duration) -> Self {
+        Self { max_clock_skew }
+    }
+}
+
+impl MessageValidator for HandshakeValidator {
+    fn validate(&self, message: &Message) -> MessageAcceptance {
+        let handshake = match serde_json::from_slice::<HandshakeMessage>(&message.data) {
+            Ok(handshake) => handshake,
+            Err(e) => {
+                let source = message.source.map_or_else(|| "unknown peer".to_string(), |peer_id| peer_id.to_string());
+                warn!("🚫 Rejecting malformed handshake from {}: {}", source, e);
+                return MessageAcceptance::Reject;
+            }
+        };
+
+        let Some(source) = message.source else {
+            warn!("🚫 Rejecting unsigned handshake claiming to be {}", handshake.peer_id);
+            return MessageAcceptance::Reject;
+        };
+        if handshake.peer_id != source.to_string() {
+            warn!("🚫 Rejecting handshake from {} claiming to be {}", source, handshake.peer_id);
+            return MessageAcceptance::Reject;
+        }
+
+        let now = unix_time();
+        if now.abs_diff(handshake.timestamp) > self.max_clock_skew.as_secs() {
+            warn!(
+                "🚫 Rejecting handshake from {} with timestamp {} ({}s from our clock)",
+                source,
+                handshake.timestamp,
+                now.abs_diff(handshake.timestamp)
+            );
+            return MessageAcceptance::Reject;
+        }
+
+        MessageAcceptance::Accept
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p::{gossipsub::IdentTopic, PeerId};
+
+    const SKEW: Duration = Duration::from_secs(60);
+
+    fn handshake(peer_id: &PeerId, timestamp: u64) -> Vec<u8> {
+        let handshake = HandshakeMessage {
+            node_name: Some("test".to_string()),
+            peer_id: peer_id.to_string(),
+            timestamp,
+            message: "hello".to_string(),
+            leaving: false,
+        };
+        serde_json::to_vec(&handshake).unwrap()
+    }
+
+    fn message(source: Option<PeerId>, data: Vec<u8>) -> Message {
+        Message { source, data, sequence_number: Some(1), topic: IdentTopic::new("handshakes").hash() }
+    }
+
+    fn validate(message: &Message) -> MessageAcceptance {
+        HandshakeValidator::new(SKEW).validate(message)
+    }
+
+    #[test]
+    fn accepts_a_fresh_handshake_from_its_signer() {
+        let peer = PeerId::random();
+        let result = validate(&message(Some(peer), handshake(&peer, unix_time())));
+        assert!(matches!(result, MessageAcceptance::Accept), "{:?}", result);
+    }
+
+    #[test]
+    fn rejects_bad_json() {
+        let result = validate(&message(Some(PeerId::random()), b"{ not json".to_vec()));
+        assert!(matches!(result, MessageAcceptance::Reject), "{:?}", result);
+    }
+
+    #[test]
+    fn rejects_a_spoofed_peer_id() {
+        let result = validate(&message(Some(PeerId::random()), handshake(&PeerId::random(), unix_time())));
+        assert!(matches!(result, MessageAcceptance::Reject), "{:?}", result);
+    }
+
+    #[test]
+    fn rejects_an_unsigned_handshake() {
+        let result = validate(&message(None, handshake(&PeerId::random(), unix_time())));
+        assert!(matches!(result, MessageAcceptance::Reject), "{:?}", result);
+    }
+
+    #[test]
+    fn rejects_timestamps_outside_the_allowed_skew() {
+        let peer = PeerId::random();
+        let skew = SKEW.as_secs();
+        for timestamp in [unix_time() - skew - 5, unix_time() + skew + 5] {
+            let result = validate(&message(Some(peer), handshake(&peer, timestamp)));
+            assert!(matches!(result, MessageAcceptance::Reject), "{}: {:?}", timestamp, result);
+        }
+        for timestamp in [unix_time() - skew + 5, unix_time() + skew - 5] {
+            let result = validate(&message(Some(peer), handshake(&peer, timestamp)));
+            assert!(matches!(result, MessageAcceptance::Accept), "{}: {:?}", timestamp, result);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+//! Setup shared by the integration tests: local nodes that find each other
+//! only through explicit dials.
+
+// Every test crate compiles this module but uses only part of it
+#![allow(dead_code)]
+
+use libp2p::{Multiaddr, PeerId};
+use node_eeb::{EventStream, NodeConfig, NodeConfigBuilder, NodeEvent, NodeHandle, P2PNode};
+use std::time::Duration;
+use tokio::time::timeout;
+
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A node on a random loopback port without bootstrap peers or mDNS.
+pub fn local_node() -> NodeConfigBuilder {
+    NodeConfig::builder()
+        .listen_addrs(vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()])
+        .use_bootstrap(false)
+        .enable_mdns(false)
+}
+
+/// Two running nodes, `dialer` connected to `node`.
+pub struct ConnectedPair {
+    pub node: NodeHandle,
+    pub dialer: NodeHandle,
+    /// Protocols `node` advertised to `dialer` through identify.
+    pub protocols: Vec<String>,
+}
+
+impl ConnectedPair {
+    pub async fn shutdown(&self) {
+        shutdown(&self.node).await;
+        shutdown(&self.dialer).await;
+    }
+}
+
+/// Start a node from `config` and return it once it listens, with its
+/// event stream and the address to dial it on.
+pub async fn start(config: NodeConfigBuilder) -> (NodeHandle, EventStream, Multiaddr) {
+    let mut node = P2PNode::new(config.build().unwrap()).await.unwrap();
+    let handle = node.handle();
+    let mut events = node.subscribe_events();
+    tokio::spawn(async move { node.run().await });
+
+    let address = timeout(TIMEOUT, async {
+        loop {
+            if let Some(NodeEvent::ListenAddrAdded { address }) = events.recv().await {
+                return address;
+            }
+        }
+    })
+    .await
+    .expect("node did not start listening");
+    (handle, events, address)
+}
+
+/// Start a node from `config`, connect a plain local node to it and return
+/// both once the plain node has identified the first.
+pub async fn connected_pair(config: NodeConfigBuilder) -> ConnectedPair {
+    let (node, _, address) = start(config).await;
+    let (dialer, mut dialer_events, _) = start(local_node().connect(vec![address])).await;
+
+    let protocols = timeout(TIMEOUT, identified(&mut dialer_events, node.local_peer_id()))
+        .await
+        .expect("node was not identified");
+
+    ConnectedPair { node, dialer, protocols }
+}
+
+async fn identified(events: &mut EventStream, peer: PeerId) -> Vec<String> {
+    loop {
+        match events.recv().await {
+            Some(NodeEvent::PeerIdentified { peer_id, protocols, .. }) if peer_id == peer => return protocols,
+            Some(_) => {}
+            None => panic!("dialer stopped before identifying the node"),
+        }
+    }
+}
+
+pub async fn shutdown(handle: &NodeHandle) {
+    let _ = timeout(TIMEOUT, handle.shutdown()).await;
+}
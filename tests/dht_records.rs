@@ -0,0 +1,57 @@
+//! Records put through the node API keep the publisher they were put with.
+
+mod common;
+
+use common::{connected_pair, local_node};
+use libp2p::PeerId;
+use node_eeb::{GetRecordOutcome, PutRecordOptions, PutRecordOutcome, RecordQuorum};
+
+/// Put a record from the dialer with `publisher` and return the publisher
+/// the other node sees, together with the dialer's own ID.
+async fn stored_publisher(publisher: Option<PeerId>) -> (Option<PeerId>, PeerId) {
+    let pair = connected_pair(local_node()).await;
+    let (node, dialer) = (&pair.node, &pair.dialer);
+    let options = PutRecordOptions { quorum: RecordQuorum::One, ttl: None, publisher };
+    let outcome = dialer.put_record("dataset", "v1", options).await.unwrap();
+    assert!(matches!(outcome, PutRecordOutcome::Stored), "{:?}", outcome);
+
+    let outcome = node.get_record("dataset", RecordQuorum::One).await.unwrap();
+    let GetRecordOutcome::Found { records } = outcome else {
+        panic!("record not found: {:?}", outcome);
+    };
+    let dialer_id = dialer.local_peer_id();
+
+    pair.shutdown().await;
+    (records[0].publisher, dialer_id)
+}
+
+#[tokio::test]
+async fn publishes_under_own_id_by_default() {
+    let (publisher, dialer) = stored_publisher(None).await;
+    assert_eq!(publisher, Some(dialer));
+}
+
+#[tokio::test]
+async fn keeps_the_requested_publisher() {
+    let requested = PeerId::random();
+    let (publisher, _) = stored_publisher(Some(requested)).await;
+    assert_eq!(publisher, Some(requested));
+}
+
+#[tokio::test]
+async fn quorum_counts_against_the_replication_factor() {
+    let pair = connected_pair(local_node()).await;
+    let (node, dialer) = (&pair.node, &pair.dialer);
+    for publisher in [None, Some(PeerId::random())] {
+        let options = PutRecordOptions { quorum: RecordQuorum::All, ttl: None, publisher };
+        let outcome = dialer.put_record("dataset", "v1", options).await.unwrap();
+        match outcome {
+            PutRecordOutcome::QuorumFailed { stored_at, quorum } => {
+                assert_eq!(stored_at, vec![node.local_peer_id()]);
+                assert_eq!(quorum, 20);
+            }
+            PutRecordOutcome::Stored => panic!("one peer met an all quorum with publisher {:?}", publisher),
+        }
+    }
+    pair.shutdown().await;
+}
@@ -0,0 +1,46 @@
+//! Handshakes relayed through the gossipsub mesh are attributed to their author.
+
+mod common;
+
+use common::{local_node, shutdown, start, TIMEOUT};
+use node_eeb::{GossipsubSettings, NodeConfigBuilder, NodeEvent};
+use std::time::Duration;
+use tokio::time::timeout;
+
+/// A local node that forms its mesh and broadcasts handshakes quickly.
+fn chatty_node() -> NodeConfigBuilder {
+    local_node()
+        .enable_dht(false)
+        .gossipsub(GossipsubSettings { heartbeat_interval: Duration::from_millis(200), ..Default::default() })
+        .handshake_interval(Duration::from_millis(500))
+}
+
+#[tokio::test]
+async fn relayed_handshake_names_its_publisher() {
+    // author <- relay <- listener, so the listener only hears the author through the relay
+    let (author, _, author_addr) = start(chatty_node()).await;
+    let (relay, _, relay_addr) = start(chatty_node().connect(vec![author_addr])).await;
+    let (listener, mut events, _) = start(chatty_node().connect(vec![relay_addr])).await;
+
+    let author_id = author.local_peer_id();
+    let peer_id = timeout(TIMEOUT * 3, async {
+        loop {
+            match events.recv().await {
+                Some(NodeEvent::HandshakeReceived { peer_id, handshake })
+                    if handshake.peer_id == author_id.to_string() =>
+                {
+                    return peer_id
+                }
+                Some(_) => {}
+                None => panic!("listener stopped"),
+            }
+        }
+    })
+    .await
+    .expect("author's handshake never reached the listener");
+    assert_eq!(peer_id, author_id);
+
+    for node in [&author, &relay, &listener] {
+        shutdown(node).await;
+    }
+}
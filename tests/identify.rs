@@ -0,0 +1,30 @@
+//! The protocols a node advertises through identify follow its configuration.
+
+mod common;
+
+use common::{connected_pair, local_node};
+
+const KAD_PROTOCOL: &str = "/ipfs/kad/1.0.0";
+const RELAY_HOP_PROTOCOL: &str = "/libp2p/circuit/relay/0.2.0/hop";
+
+/// Start a node configured with `dht` and `relay`, connect a plain node to
+/// it and return the protocols it advertised to that node.
+async fn advertised_protocols(dht: bool, relay: bool) -> Vec<String> {
+    let pair = connected_pair(local_node().enable_dht(dht).relay_server(relay)).await;
+    pair.shutdown().await;
+    pair.protocols
+}
+
+#[tokio::test]
+async fn advertises_enabled_protocols() {
+    let protocols = advertised_protocols(true, true).await;
+    assert!(protocols.iter().any(|p| p == KAD_PROTOCOL), "{:?}", protocols);
+    assert!(protocols.iter().any(|p| p == RELAY_HOP_PROTOCOL), "{:?}", protocols);
+}
+
+#[tokio::test]
+async fn omits_disabled_protocols() {
+    let protocols = advertised_protocols(false, false).await;
+    assert!(!protocols.iter().any(|p| p == KAD_PROTOCOL), "{:?}", protocols);
+    assert!(!protocols.iter().any(|p| p == RELAY_HOP_PROTOCOL), "{:?}", protocols);
+}
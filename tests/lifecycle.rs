@@ -0,0 +1,120 @@
+//! Handles, event streams and HTTP servers after the node has stopped.
+
+use node_eeb::{NodeConfig, NodeHandle, NodeStopped, P2PNode};
+use std::{
+    net::{SocketAddr, TcpListener},
+    time::Duration,
+};
+use tokio::time::timeout;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+async fn local_node() -> P2PNode {
+    let config = NodeConfig::builder()
+        .listen_addrs(vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()])
+        .use_bootstrap(false)
+        .enable_mdns(false)
+        .shutdown_timeout(Duration::from_secs(1))
+        .build()
+        .unwrap();
+    P2PNode::new(config).await.unwrap()
+}
+
+fn assert_stopped(result: anyhow::Result<impl std::fmt::Debug>) {
+    let error = result.expect_err("call succeeded on a stopped node");
+    assert!(error.is::<NodeStopped>(), "unexpected error: {:#}", error);
+}
+
+#[tokio::test]
+async fn calls_fail_once_run_returns() {
+    let mut node = local_node().await;
+    let handle = node.handle();
+    let mut events = node.subscribe_events();
+
+    let stop = {
+        let handle = handle.clone();
+        async move { timeout(TIMEOUT, handle.shutdown()).await.unwrap() }
+    };
+    let (result, shutdown) = tokio::join!(timeout(TIMEOUT, node.run()), stop);
+    result.unwrap().unwrap();
+    shutdown.unwrap();
+
+    // The node is still alive here, yet nothing answers handles any more
+    assert_stopped(timeout(TIMEOUT, handle.connected_peers()).await.unwrap());
+    assert_stopped(timeout(TIMEOUT, node.handle().topics()).await.unwrap());
+
+    let drained = timeout(TIMEOUT, async { while events.recv().await.is_some() {} }).await;
+    assert!(drained.is_ok(), "event stream did not end");
+    assert!(timeout(TIMEOUT, node.subscribe_events().recv()).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn calls_made_before_run_are_answered() {
+    let mut node = local_node().await;
+    let handle: NodeHandle = node.handle();
+    let peers = tokio::spawn({
+        let handle = handle.clone();
+        async move { handle.connected_peers().await }
+    });
+
+    let stop = async {
+        let peers = timeout(TIMEOUT, peers).await.unwrap().unwrap().unwrap();
+        handle.shutdown().await.unwrap();
+        peers
+    };
+    let (result, peers) = tokio::join!(timeout(TIMEOUT, node.run()), stop);
+    result.unwrap().unwrap();
+    assert!(peers.is_empty());
+}
+
+#[tokio::test]
+async fn shutdown_fails_queued_calls_and_answers_queued_shutdowns() {
+    let mut node = local_node().await;
+    let handle = node.handle();
+
+    // Queued behind the first shutdown, so the loop sees them only while stopping
+    let calls = async {
+        tokio::join!(handle.shutdown(), handle.connected_peers(), handle.shutdown())
+    };
+    let (result, calls) = tokio::join!(timeout(TIMEOUT, node.run()), timeout(TIMEOUT, calls));
+    result.unwrap().unwrap();
+    let (first, peers, second) = calls.expect("calls were left waiting");
+    first.unwrap();
+    assert_stopped(peers);
+    second.unwrap();
+}
+
+/// A localhost address nothing listens on right now.
+fn free_addr() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap()
+}
+
+#[tokio::test]
+async fn http_servers_release_their_ports_once_run_returns() {
+    let (admin, metrics) = (free_addr(), free_addr());
+    let config = NodeConfig::builder()
+        .listen_addrs(vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()])
+        .use_bootstrap(false)
+        .enable_mdns(false)
+        .shutdown_timeout(Duration::from_secs(1))
+        .admin_api(Some(admin))
+        .metrics(Some(metrics))
+        .build()
+        .unwrap();
+    let mut node = P2PNode::new(config).await.unwrap();
+    let handle = node.handle();
+
+    let stop = async {
+        // Wait until the servers are up before stopping the node
+        while TcpListener::bind(metrics).is_ok() {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        timeout(TIMEOUT, handle.shutdown()).await.unwrap()
+    };
+    let (result, shutdown) = tokio::join!(timeout(TIMEOUT, node.run()), stop);
+    result.unwrap().unwrap();
+    shutdown.unwrap();
+
+    TcpListener::bind(admin).expect("admin API still bound");
+    TcpListener::bind(metrics).expect("metrics endpoint still bound");
+}